@@ -1,94 +1,20 @@
-use crate::board::{TileKind, TileContent, Board, EntryPoint, Orientation};
+use crate::board::{TileContent, Board, EntryPoint, Orientation};
+use crate::rules::RuleSet;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::collections::HashMap;
 
-pub fn objects_reachable_in_1_move_from(board: &Board, row: usize, col: usize) -> HashMap<TileContent, Vec<EntryPoint>> {
-    // List the moves that we have to analyse.
-    let moves = match board.extra_tile().kind {
-        TileKind::Straight => {
-            vec![
-                (EntryPoint::NorthLeft, Orientation::North),
-                (EntryPoint::NorthLeft, Orientation::East),
-                (EntryPoint::NorthCenter, Orientation::North),
-                (EntryPoint::NorthCenter, Orientation::East),
-                (EntryPoint::NorthRight, Orientation::North),
-                (EntryPoint::NorthRight, Orientation::East),
-                (EntryPoint::EastUp, Orientation::North),
-                (EntryPoint::EastUp, Orientation::East),
-                (EntryPoint::EastCenter, Orientation::North),
-                (EntryPoint::EastCenter, Orientation::East),
-                (EntryPoint::EastDown, Orientation::North),
-                (EntryPoint::EastDown, Orientation::East),
-                (EntryPoint::SouthLeft, Orientation::North),
-                (EntryPoint::SouthLeft, Orientation::East),
-                (EntryPoint::SouthCenter, Orientation::North),
-                (EntryPoint::SouthCenter, Orientation::East),
-                (EntryPoint::SouthRight, Orientation::North),
-                (EntryPoint::SouthRight, Orientation::East),
-                (EntryPoint::WestUp, Orientation::North),
-                (EntryPoint::WestUp, Orientation::East),
-                (EntryPoint::WestCenter, Orientation::North),
-                (EntryPoint::WestCenter, Orientation::East),
-                (EntryPoint::WestDown, Orientation::North),
-                (EntryPoint::WestDown, Orientation::East)
-            ]
-        },
-        TileKind::Corner => {
-            vec![
-                (EntryPoint::NorthLeft, Orientation::East),
-                (EntryPoint::NorthLeft, Orientation::South),
-                (EntryPoint::NorthCenter, Orientation::East),
-                (EntryPoint::NorthCenter, Orientation::South),
-                (EntryPoint::NorthRight, Orientation::East),
-                (EntryPoint::NorthRight, Orientation::South),
-                (EntryPoint::EastUp, Orientation::South),
-                (EntryPoint::EastUp, Orientation::West),
-                (EntryPoint::EastCenter, Orientation::South),
-                (EntryPoint::EastCenter, Orientation::West),
-                (EntryPoint::EastDown, Orientation::South),
-                (EntryPoint::EastDown, Orientation::West),
-                (EntryPoint::SouthLeft, Orientation::West),
-                (EntryPoint::SouthLeft, Orientation::North),
-                (EntryPoint::SouthCenter, Orientation::West),
-                (EntryPoint::SouthCenter, Orientation::North),
-                (EntryPoint::SouthRight, Orientation::West),
-                (EntryPoint::SouthRight, Orientation::North),
-                (EntryPoint::WestUp, Orientation::North),
-                (EntryPoint::WestUp, Orientation::East),
-                (EntryPoint::WestCenter, Orientation::North),
-                (EntryPoint::WestCenter, Orientation::East),
-                (EntryPoint::WestDown, Orientation::North),
-                (EntryPoint::WestDown, Orientation::East)
-            ]
-        },
-        TileKind::Junction => {
-            vec![
-                (EntryPoint::NorthLeft, Orientation::North),
-                (EntryPoint::NorthCenter, Orientation::North),
-                (EntryPoint::NorthRight, Orientation::North),
-                (EntryPoint::EastUp, Orientation::East),
-                (EntryPoint::EastCenter, Orientation::East),
-                (EntryPoint::EastDown, Orientation::East),
-                (EntryPoint::SouthLeft, Orientation::South),
-                (EntryPoint::SouthCenter, Orientation::South),
-                (EntryPoint::SouthRight, Orientation::South),
-                (EntryPoint::WestUp, Orientation::West),
-                (EntryPoint::WestCenter, Orientation::West),
-                (EntryPoint::WestDown, Orientation::West)
-            ]
-        }
-    };
-
+pub fn objects_reachable_in_1_move_from(board: &Board, rules: &RuleSet, row: usize, col: usize) -> HashMap<TileContent, Vec<EntryPoint>> {
     // Apply each move to a copy of the board.
     let mut reachable_obj = HashMap::new();
 
-    for m in moves {
-        if Some(m.0) == board.extra_tile_position() {
-            continue;
-        }
-
-        let mut new_board = board.clone();
+    for m in board.distinct_legal_pushes(rules) {
+        let mut new_board = *board;
         let mut pos = (row, col);
-        new_board.push_tile(m.0, m.1, &mut [&mut pos]);
+        new_board.push_tile(m.0, m.1, &mut [&mut pos], rules);
         for o in new_board.objects_reachable_from(pos.0, pos.1) {
             let pushes = reachable_obj.entry(o).or_insert(Vec::new());
             if !pushes.contains(&m.0) {
@@ -99,102 +25,277 @@ pub fn objects_reachable_in_1_move_from(board: &Board, row: usize, col: usize) -
     reachable_obj
 }
 
-pub fn list_all_moves() -> Vec<(EntryPoint, Orientation)> {
-    vec![
-        (EntryPoint::NorthLeft, Orientation::North),
-        (EntryPoint::NorthLeft, Orientation::East),
-        (EntryPoint::NorthLeft, Orientation::South),
-        (EntryPoint::NorthLeft, Orientation::West),
-        (EntryPoint::NorthCenter, Orientation::North),
-        (EntryPoint::NorthCenter, Orientation::East),
-        (EntryPoint::NorthCenter, Orientation::South),
-        (EntryPoint::NorthCenter, Orientation::West),
-        (EntryPoint::NorthRight, Orientation::North),
-        (EntryPoint::NorthRight, Orientation::East),
-        (EntryPoint::NorthRight, Orientation::South),
-        (EntryPoint::NorthRight, Orientation::West),
-        (EntryPoint::EastUp, Orientation::North),
-        (EntryPoint::EastUp, Orientation::East),
-        (EntryPoint::EastUp, Orientation::South),
-        (EntryPoint::EastUp, Orientation::West),
-        (EntryPoint::EastCenter, Orientation::North),
-        (EntryPoint::EastCenter, Orientation::East),
-        (EntryPoint::EastCenter, Orientation::South),
-        (EntryPoint::EastCenter, Orientation::West),
-        (EntryPoint::EastDown, Orientation::North),
-        (EntryPoint::EastDown, Orientation::East),
-        (EntryPoint::EastDown, Orientation::South),
-        (EntryPoint::EastDown, Orientation::West),
-        (EntryPoint::SouthLeft, Orientation::North),
-        (EntryPoint::SouthLeft, Orientation::East),
-        (EntryPoint::SouthLeft, Orientation::South),
-        (EntryPoint::SouthLeft, Orientation::West),
-        (EntryPoint::SouthCenter, Orientation::North),
-        (EntryPoint::SouthCenter, Orientation::East),
-        (EntryPoint::SouthCenter, Orientation::South),
-        (EntryPoint::SouthCenter, Orientation::West),
-        (EntryPoint::SouthRight, Orientation::North),
-        (EntryPoint::SouthRight, Orientation::East),
-        (EntryPoint::SouthRight, Orientation::South),
-        (EntryPoint::SouthRight, Orientation::West),
-        (EntryPoint::WestUp, Orientation::North),
-        (EntryPoint::WestUp, Orientation::East),
-        (EntryPoint::WestUp, Orientation::South),
-        (EntryPoint::WestUp, Orientation::West),
-        (EntryPoint::WestCenter, Orientation::North),
-        (EntryPoint::WestCenter, Orientation::East),
-        (EntryPoint::WestCenter, Orientation::South),
-        (EntryPoint::WestCenter, Orientation::West),
-        (EntryPoint::WestDown, Orientation::North),
-        (EntryPoint::WestDown, Orientation::East),
-        (EntryPoint::WestDown, Orientation::South),
-        (EntryPoint::WestDown, Orientation::West)
-    ]
+// Whether one of the objects can be reached in one move from each of the
+// given positions. Paths go both ways, so it is enough to look at the cells
+// reachable from the objects.
+fn object_reachable_in_1_move(board: &Board, rules: &RuleSet, positions: &[(usize, usize)], objs: &[TileContent]) -> Vec<bool> {
+    let mut res = vec![false; positions.len()];
+    let n = board.size();
+
+    for m in board.distinct_legal_pushes(rules) {
+        let mut new_board = *board;
+        let mut new_positions = positions.to_vec();
+        let mut positions_itermut: Vec<_> = new_positions.iter_mut().collect();
+        new_board.push_tile(m.0, m.1, &mut positions_itermut, rules);
+
+        let mask = objs.iter().filter_map(|o| new_board.content_position(*o))
+            .fold(0, |mask, (row, col)| mask | new_board.reachable_mask(row, col));
+        for (i, p) in new_positions.iter().enumerate() {
+            res[i] |= mask & 1 << (p.0*n + p.1) != 0;
+        }
+    }
+    res
 }
 
-// For each own move, analyzes a set of scenarios (opponent moves) and returns
-// the number of times that the target object will be reachable in that scenario.
-pub fn object_reachable_scenarios(board: &Board, row: usize, col: usize, obj: TileContent) -> HashMap<((EntryPoint, Orientation), (usize, usize)), usize> {
-    // List all possible first moves.
-    let moves = list_all_moves();
+pub type Scenario = ((EntryPoint, Orientation), (usize, usize));
 
-    // Here we will store, for each move, the number of scenarios in
-    // which the target is reachable on the next move.
-    let mut reachable_target_count = HashMap::new();
+// Number of random scenarios analyzed when there are several opponents.
+const NUM_SAMPLES: usize = 50;
 
-    // Apply each move to a copy of the board.
-    for m in &moves {
-        if Some(m.0) == board.extra_tile_position() {
-            continue;
+// The sequences of pushes that the opponents can make before the next turn:
+// all of them if there is only one opponent, otherwise a random sample.
+fn opponent_pushes<R: Rng>(board: &Board, rules: &RuleSet, num_opponents: usize, rng: &mut R) -> Vec<Vec<(EntryPoint, Orientation)>> {
+    if num_opponents == 1 {
+        return board.legal_pushes(rules).into_iter().map(|m| vec![m]).collect();
+    }
+
+    (0..NUM_SAMPLES).map(|_| {
+        let mut board = *board;
+        (0..num_opponents).map(|_| {
+            let m = *board.legal_pushes(rules).choose(rng).unwrap();
+            board.push_tile(m.0, m.1, &mut [], rules);
+            m
+        }).collect()
+    }).collect()
+}
+
+// For each own move, analyzes a set of scenarios (the pushes of the opponents
+// before the next turn) and returns the probability that one of the target
+// objects will be reachable on the next turn.
+pub fn object_reachable_scenarios(board: &Board, rules: &RuleSet, row: usize, col: usize, objs: &[TileContent], num_opponents: usize) -> HashMap<Scenario, f64> {
+    #[cfg(feature = "parallel")]
+    let res = parallel_scenarios(board, rules, (row, col), objs, num_opponents);
+    #[cfg(not(feature = "parallel"))]
+    let res = sequential_scenarios(board, rules, (row, col), objs, num_opponents);
+    res
+}
+
+// The scenarios of all the own pushes, analyzed one after the other.
+#[cfg(any(test, not(feature = "parallel")))]
+fn sequential_scenarios(board: &Board, rules: &RuleSet, pos: (usize, usize), objs: &[TileContent], num_opponents: usize) -> HashMap<Scenario, f64> {
+    board.distinct_legal_pushes(rules).iter().enumerate()
+        .flat_map(|(i, m)| push_scenarios(board, rules, pos, objs, num_opponents, *m, i as u64))
+        .collect()
+}
+
+// The pushes are independent from each other, so they can also be analyzed
+// in parallel. Each one samples its scenarios from its own seed, so that the
+// results are the same either way.
+#[cfg(feature = "parallel")]
+fn parallel_scenarios(board: &Board, rules: &RuleSet, pos: (usize, usize), objs: &[TileContent], num_opponents: usize) -> HashMap<Scenario, f64> {
+    let results: Vec<_> = board.distinct_legal_pushes(rules).par_iter().enumerate()
+        .map(|(i, m)| push_scenarios(board, rules, pos, objs, num_opponents, *m, i as u64))
+        .collect();
+    results.into_iter().flatten().collect()
+}
+
+// The scenarios of object_reachable_scenarios for one of the own moves.
+fn push_scenarios(board: &Board, rules: &RuleSet, pos: (usize, usize), objs: &[TileContent], num_opponents: usize, m: (EntryPoint, Orientation), seed: u64) -> Vec<(Scenario, f64)> {
+    // Always sample the same scenarios, so that the analysis of a given
+    // position does not change.
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // Apply the move to a copy of the board.
+    let mut new_board = *board;
+    let mut pos = pos;
+    new_board.push_tile(m.0, m.1, &mut [&mut pos], rules);
+
+    // List all positions reachable from pos and collect them in a vector.
+    let reach = new_board.reachable_from(pos.0, pos.1);
+    let reach: Vec<_> = reach.into_iter().collect();
+    let mut count = vec![0; reach.len()];
+
+    // Apply the scenarios to the board and add 1 to the count if in that
+    // scenario a target object is reachable in 1 move from the (possibly
+    // shifted) reachable position.
+    let scenarios = opponent_pushes(&new_board, rules, num_opponents, &mut rng);
+    for pushes in &scenarios {
+        let mut new_board2 = new_board;
+        let mut reach2 = reach.clone();
+        for m2 in pushes {
+            let mut reach2_itermut: Vec<_> = reach2.iter_mut().collect();
+            new_board2.push_tile(m2.0, m2.1, &mut reach2_itermut, rules);
         }
 
-        let mut new_board = board.clone();
+        for (i, reachable) in object_reachable_in_1_move(&new_board2, rules, &reach2, objs).into_iter().enumerate() {
+            if reachable {
+                count[i] += 1;
+            }
+        }
+    }
+
+    // The fraction of the scenarios in which the target is reachable.
+    reach.into_iter().zip(count).map(|(p, n)| ((m, p), n as f64 / scenarios.len() as f64)).collect()
+}
+
+// What a player should do to get to one of its objectives.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hint {
+    pub push: (EntryPoint, Orientation),
+    pub cell: (usize, usize),
+    // The objective reached by moving to the cell, if any.
+    pub reached: Option<TileContent>,
+    // Otherwise, the probability that an objective can be reached on the
+    // next turn, over the pushes of the opponents that were considered.
+    pub probability: f64
+}
+
+// The best push and move towards any of the objects, for a player at the
+// given position.
+pub fn hint(board: &Board, rules: &RuleSet, row: usize, col: usize, objs: &[TileContent], num_opponents: usize) -> Hint {
+    hint_with(board, rules, (row, col), objs, num_opponents, &mut |b, (row, col)| b.reachable_mask(row, col))
+}
+
+// The hint function, looking up the cells reachable after each own push with
+// the given function.
+fn hint_with(board: &Board, rules: &RuleSet, (row, col): (usize, usize), objs: &[TileContent], num_opponents: usize, reachable_mask: &mut dyn FnMut(&Board, (usize, usize)) -> u128) -> Hint {
+    let size = board.size();
+    for (entry, orientation) in board.distinct_legal_pushes(rules) {
+        let mut new_board = *board;
         let mut pos = (row, col);
-        new_board.push_tile(m.0, m.1, &mut [&mut pos]);
+        new_board.push_tile(entry, orientation, &mut [&mut pos], rules);
+        let reachable = reachable_mask(&new_board, pos);
+        for &obj in objs {
+            if let Some(cell) = new_board.content_position(obj).filter(|p| reachable & 1 << (p.0*size + p.1) != 0) {
+                return Hint { push: (entry, orientation), cell, reached: Some(obj), probability: 1.0 };
+            }
+        }
+    }
+
+    // Look one move ahead, preferring the first pushes (clockwise) when
+    // several are as good.
+    let mut scenarios: Vec<_> = object_reachable_scenarios(board, rules, row, col, objs, num_opponents).into_iter().collect();
+    scenarios.sort_by_key(|&(((entry, orientation), cell), _)| (entry.to_num(size), orientation as usize, cell));
+    let ((push, cell), probability) = scenarios.into_iter().rev()
+        .max_by(|(_, p1), (_, p2)| f64::total_cmp(p1, p2)).unwrap();
+
+    Hint { push, cell, reached: None, probability }
+}
 
-        // List all positions reachable from pos and collect them in a vector.
-        let reach = new_board.reachable_from(pos.0, pos.1);
-        let reach: Vec<_> = reach.into_iter().collect();
+// The board, rules of the pushes, position, objectives and number of
+// opponents of a hint.
+type HintKey = (u64, (bool, bool), (usize, usize), Vec<TileContent>, usize);
+
+// A cache of reachable cells and hints, keyed by the hash of the board and
+// the position of the player, so that nothing is computed twice for the same
+// position, e.g. when a hint is asked again in the same turn. Hints look up
+// the cells reachable after each own push here, but not in the scenarios of
+// the opponents: the extra tile moves with each push, so those boards are
+// almost all different. When a map holds too many results it is emptied.
+pub struct Cache {
+    capacity: usize,
+    reachable: HashMap<(u64, (usize, usize)), u128>,
+    hints: HashMap<HintKey, Hint>
+}
+
+impl Cache {
+    pub fn new(capacity: usize) -> Cache {
+        Cache { capacity, reachable: HashMap::new(), hints: HashMap::new() }
+    }
 
-        for i in 1..reach.len() {
-            reachable_target_count.insert((*m, reach[i]), 0);
+    pub fn len(&self) -> usize {
+        self.reachable.len() + self.hints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.reachable.clear();
+        self.hints.clear();
+    }
+
+    // The cells reachable from a given position, as returned by
+    // Board::reachable_mask.
+    pub fn reachable_mask(&mut self, board: &Board, row: usize, col: usize) -> u128 {
+        let key = (board.zobrist_hash(), (row, col));
+        if let Some(mask) = self.reachable.get(&key) {
+            return *mask;
         }
 
-        // Apply the scenarios to the board and add 1 to the count if in that
-        // scenario the target object is reachable in 1 move from the (possibly
-        // shifted) reachable position.
-        for m2 in &moves {
-            let mut new_board2 = new_board.clone();
-            let mut reach2 = reach.clone();
-            let mut reach2_itermut: Vec<_> = reach2.iter_mut().collect();
-            new_board2.push_tile(m2.0, m2.1, &mut reach2_itermut);
+        let mask = board.reachable_mask(row, col);
+        if self.reachable.len() >= self.capacity {
+            self.reachable.clear();
+        }
+        self.reachable.insert(key, mask);
+        mask
+    }
 
-            for i in 1..reach2.len() {
-                if objects_reachable_in_1_move_from(&new_board2, reach2[i].0, reach2[i].1).contains_key(&obj) {
-                    *reachable_target_count.get_mut(&(*m, reach[i])).unwrap() += 1;
-                }
-            }
+    // The same as the hint function.
+    pub fn hint(&mut self, board: &Board, rules: &RuleSet, row: usize, col: usize, objs: &[TileContent], num_opponents: usize) -> Hint {
+        let key = (board.zobrist_hash(), (rules.reverse_push, rules.wrap_around), (row, col), objs.to_vec(), num_opponents);
+        if let Some(hint) = self.hints.get(&key) {
+            return *hint;
+        }
+
+        let hint = hint_with(board, rules, (row, col), objs, num_opponents, &mut |b, (row, col)| self.reachable_mask(b, row, col));
+        if self.hints.len() >= self.capacity {
+            self.hints.clear();
+        }
+        self.hints.insert(key, hint);
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn cache_keeps_hints() {
+        let game = Game::with_seed(&['r', 'b', 'y'], 4).unwrap();
+        let (row, col) = game.current_position();
+        let objs = game.current_objectives();
+
+        let mut cache = Cache::new(1000);
+        let first = cache.hint(game.board(), game.rules(), row, col, &objs, 2);
+        assert_eq!(first, hint(game.board(), game.rules(), row, col, &objs, 2));
+        // No objective is reachable now, so the cells reachable after each
+        // own push are stored, together with the hint.
+        assert!(first.reached.is_none());
+        let len = cache.len();
+        assert_eq!(len, game.board().distinct_legal_pushes(game.rules()).len() + 1);
+
+        let second = cache.hint(game.board(), game.rules(), row, col, &objs, 2);
+        assert_eq!(second, first);
+        assert_eq!(cache.len(), len);
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let mut cache = Cache::new(10);
+        for seed in 0..5 {
+            let game = Game::with_seed(&['r', 'b'], seed).unwrap();
+            let (row, col) = game.current_position();
+            cache.hint(game.board(), game.rules(), row, col, &game.current_objectives(), 1);
+            assert!(cache.len() <= 20);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_matches_sequential() {
+        for &(players, seed) in &[(&['r', 'b'][..], 1), (&['r', 'b'][..], 2), (&['r', 'b', 'y', 'g'][..], 3)] {
+            let game = Game::with_seed(players, seed).unwrap();
+            let pos = game.current_position();
+            let objs = game.current_objectives();
+            let num_opponents = players.len() - 1;
+            assert_eq!(
+                parallel_scenarios(game.board(), game.rules(), pos, &objs, num_opponents),
+                sequential_scenarios(game.board(), game.rules(), pos, &objs, num_opponents)
+            );
         }
     }
-    reachable_target_count
 }
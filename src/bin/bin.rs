@@ -1,9 +1,463 @@
-use labyrinth_game::Game;
+use labyrinth_game::{Action, Game, GameError, RuleSet, Variant};
+use labyrinth_game::analyzer::Cache;
+use labyrinth_game::board::{TileContent, Board, EntryPoint, Orientation};
+use labyrinth_game::game::MoveOutcome;
+use labyrinth_game::mcts::{Budget, MctsStrategy};
+use labyrinth_game::record::Record;
+use labyrinth_game::strategy::{GreedyStrategy, RandomStrategy, Strategy};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// The players played by the computer, by tag.
+type Bots = HashMap<char, Box<dyn Strategy>>;
 
 fn main() {
-    let mut g = Game::new();
+    // A seed can be given to replay the same board and objectives,
+    // or a saved game can be resumed. House rules can be read from a file,
+    // and some of them can be given directly.
+    let args: Vec<String> = std::env::args().collect();
+    let (mut seed, mut size, mut load, mut show_route, mut hot_seat) = (None, None, None, false, false);
+    let (mut rules_path, mut any_order, mut objectives) = (None, false, None);
+    let mut it = args[1..].iter();
+    while let Some(opt) = it.next() {
+        match opt.as_str() {
+            "--route" => show_route = true,
+            "--hot-seat" => hot_seat = true,
+            "--any-order" => any_order = true,
+            _ => match (opt.as_str(), it.next()) {
+                ("--seed", Some(s)) if s.parse::<u64>().is_ok() => seed = s.parse().ok(),
+                ("--size", Some(s)) if s.parse().map(Board::is_valid_size) == Ok(true) => size = s.parse().ok(),
+                ("--objectives", Some(s)) if s.split(',').all(|n| n.parse::<usize>().is_ok_and(|n| n > 0)) => {
+                    objectives = Some(s.split(',').map(|n| n.parse().unwrap()).collect());
+                },
+                ("--rules", Some(path)) => rules_path = Some(path),
+                ("--load", Some(path)) => load = Some(path),
+                _ => {
+                    println!("Usage: {} [--seed <seed>] [--rules <file>] [--size <5, 7 or 9>] [--objectives <n or n,n,...>] [--load <saved game>] [--route] [--hot-seat] [--any-order]", args[0]);
+                    return;
+                }
+            }
+        }
+    }
 
+    let mut rules = match rules_path.map(RuleSet::load) {
+        None => RuleSet::default(),
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if let Some(size) = size {
+        rules.size = size;
+    }
+    if any_order {
+        rules.variant = Variant::AnyOrder;
+    }
+    if let Some(objectives) = objectives {
+        rules.objectives = objectives;
+    }
+
+    let (mut g, mut bots) = match load {
+        None => match new_game(seed, &rules) {
+            Some(x) => x,
+            None => return
+        },
+        Some(path) => match Game::load(path) {
+            Ok(g) => {
+                let bots = loaded_game_bots(&g);
+                (g, bots)
+            },
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    };
+    println!("Playing game with seed {}.", g.seed());
+    println!();
+
+    // Keep the hints, as they can be asked again after undoing.
+    let mut cache = Cache::new(1000);
+    // With "--route", the path of the last move is shown on the board.
+    // With "--hot-seat", the players share the same screen, which is cleared
+    // before and after each turn so that the objectives stay secret. The
+    // current player can also change after "undo" or "redo".
+    let mut route = vec![];
+    let (mut hidden, mut last_player) = (true, None);
     while !g.is_over() {
-        g.play_turn();
+        let tag = g.current_player();
+        if last_player != Some(tag) {
+            hidden = true;
+            last_player = Some(tag);
+        }
+        if hot_seat && hidden && !bots.contains_key(&tag) {
+            clear_screen();
+            println!("Press enter, {}.", colored_name(tag));
+            read_line();
+            hidden = false;
+        }
+
+        println!("{}", g.draw_to_string_with_path(&route));
+        route = if let Some(bot) = bots.get_mut(&tag) {
+            bot_turn(&mut g, bot.as_mut())
+        } else if g.has_pushed() {
+            let route = get_move(&mut g, &bots);
+            if hot_seat && !g.is_over() && g.current_player() != tag {
+                // Let the player read the outcome of the move first.
+                println!("Press enter to hide your objectives.");
+                read_line();
+                clear_screen();
+            }
+            route
+        } else {
+            get_push(&mut g, &bots, &mut cache);
+            vec![]
+        };
+        if !show_route {
+            route.clear();
+        }
+    }
+}
+
+fn colored_name(tag: char) -> String {
+    match tag {
+        'r' => "\x1B[1;31mRed\x1B[0m".to_string(),
+        'b' => "\x1B[1;34mBlue\x1B[0m".to_string(),
+        'y' => "\x1B[1;33mYellow\x1B[0m".to_string(),
+        'g' => "\x1B[1;32mGreen\x1B[0m".to_string(),
+        _ => panic!()
+    }
+}
+
+fn clear_screen() {
+    print!("\x1B[2J\x1B[1;1H");
+}
+
+fn read_line() -> String {
+    let mut s = String::new();
+
+    let n = std::io::stdin().read_line(&mut s)
+        .expect("Failed to read line");
+
+    // Stop playing when the input is closed.
+    if n == 0 {
+        std::process::exit(0);
+    }
+
+    s
+}
+
+fn action_to_string(action: Action, size: usize) -> String {
+    match action {
+        Action::Push(..) => format!("push \"{}\"", action.notation(size)),
+        Action::Move((row, col)) => format!("move to \"{} {}\"", row+1, col+1)
+    }
+}
+
+// Handle the "undo", "redo", "save" and "record" commands, returning
+// whether the input was one of them. The turns of the bots are undone and
+// redone together with the action before them.
+fn command(g: &mut Game, s: &str, bots: &Bots) -> bool {
+    let words: Vec<_> = s.split_whitespace().collect();
+    match words.as_slice() {
+        ["save", path] => match g.save(path) {
+            Ok(()) => println!("Game saved to {}. Resume it with \"--load {}\".", path, path),
+            Err(e) => println!("Cannot save the game: {}", e)
+        },
+        ["record", path] => match std::fs::write(path, Record::from_game(g).to_string()) {
+            Ok(()) => println!("Game record written to {}.", path),
+            Err(e) => println!("Cannot write the game record: {}", e)
+        },
+        [c] if c.eq_ignore_ascii_case("undo") => match g.undo() {
+            Some(action) => {
+                println!("Undoing the {}.", action_to_string(action, g.board().size()));
+                while bots.contains_key(&g.current_player()) && g.undo().is_some() {}
+            },
+            None => println!("There is nothing to undo!")
+        },
+        [c] if c.eq_ignore_ascii_case("redo") => match g.redo() {
+            Some(action) => {
+                println!("Redoing the {}.", action_to_string(action, g.board().size()));
+                while bots.contains_key(&g.current_player()) && g.redo().is_some() {}
+            },
+            None => println!("There is nothing to redo!")
+        },
+        _ => return false
+    }
+    println!();
+    true
+}
+
+fn new_bot(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "bot" => Some(Box::new(GreedyStrategy::new())),
+        "mcts" => Some(Box::new(MctsStrategy::new(Budget::Time(Duration::from_secs(1))))),
+        "random" => Some(Box::new(RandomStrategy::new())),
+        _ => None
+    }
+}
+
+// Read a list of players such as "r,b:bot", with the bots among them.
+fn read_players() -> Option<(Vec<char>, Bots)> {
+    let players_str = read_line();
+
+    let mut err = 0;
+    let mut bots = Bots::new();
+    let players: Vec<_> = players_str.trim().split(',').filter_map(|x| {
+        let x = x.trim().to_ascii_lowercase();
+        let (tag, bot) = match x.find(':') {
+            Some(i) => (&x[..i], Some(&x[i+1..])),
+            None => (&x[..], None)
+        };
+        let mut chars = tag.chars();
+        match (chars.next(), chars.next(), bot.map(new_bot)) {
+            (Some(c), None, None) => Some(c),
+            (Some(c), None, Some(Some(bot))) => {
+                bots.insert(c, bot);
+                Some(c)
+            },
+            _ => {
+                err += 1;
+                println!("{} is not a valid player!", x);
+                None
+            }
+        }
+    }).collect();
+
+    if err > 0 {
+        return None;
+    }
+    Some((players, bots))
+}
+
+// Ask for the players until a game can be started with them, or return
+// nothing if the rules do not allow any game.
+fn new_game(seed: Option<u64>, rules: &RuleSet) -> Option<(Game, Bots)> {
+    loop {
+        println!("Who is playing?");
+        println!("(for example: enter 'r,b' if the red and blue players are playing,");
+        println!(" or 'r,b:bot' to play against the computer)");
+        let (players, bots) = match read_players() {
+            Some(x) => x,
+            None => continue
+        };
+
+        match Game::with_rules(&players, rules, seed.unwrap_or_else(rand::random)) {
+            Ok(g) => {
+                println!();
+                return Some((g, bots));
+            },
+            // Only these errors can be fixed by choosing other players.
+            Err(e @ GameError::InvalidPlayer(_)) | Err(e @ GameError::DuplicatePlayer(_))
+            | Err(e @ GameError::PlayerCount(_)) | Err(e @ GameError::ObjectiveLists(_)) => println!("{}", e),
+            Err(e) => {
+                println!("{}", e);
+                return None;
+            }
+        }
+    }
+}
+
+// Saved games do not say which players are bots, so ask again.
+fn loaded_game_bots(g: &Game) -> Bots {
+    let mut tags = g.players();
+    tags.sort();
+    let tags_str: Vec<_> = g.players().iter().map(|t| t.to_string()).collect();
+
+    loop {
+        println!("Who is playing? The players of the saved game are {}.", tags_str.join(","));
+        println!("(for example: enter '{}' if they are all human,", tags_str.join(","));
+        println!(" or add ':bot' after the players played by the computer)");
+        let (mut players, bots) = match read_players() {
+            Some(x) => x,
+            None => continue
+        };
+
+        players.sort();
+        if players == tags {
+            println!();
+            return bots;
+        }
+        println!("The players should be those of the saved game!");
+    }
+}
+
+// Let a bot play the rest of its turn, returning the path of its move.
+fn bot_turn(g: &mut Game, bot: &mut dyn Strategy) -> Vec<(usize, usize)> {
+    let tag = g.current_player();
+    let size = g.board().size();
+
+    if !g.has_pushed() {
+        let (entry, orientation) = bot.choose_push(&g.current_view());
+        g.apply_push(entry, orientation).expect("bots only make legal pushes");
+        println!("Player {} pushes \"{}{}\".", colored_name(tag), entry.to_num(size), orientation.to_char());
+    }
+
+    let (row, col) = g.current_position();
+    let reachable = g.board().reachable_from(row, col);
+    let next_pos = bot.choose_move(&g.current_view(), &reachable);
+    println!("Player {} moves to \"{} {}\".", colored_name(tag), next_pos.0+1, next_pos.1+1);
+    println!();
+    let route = g.board().shortest_path((row, col), next_pos).unwrap();
+
+    match g.apply_move(next_pos).expect("bots only make legal moves") {
+        MoveOutcome::Moved => {},
+        MoveOutcome::Collected { objective, .. } => {
+            println!("Player {} reached the \x1B[1m{:?}\x1B[0m!", colored_name(tag), objective);
+            println!();
+        },
+        MoveOutcome::Won => {
+            println!("{}", g.draw_to_string());
+            println!("Player {} wins the game!", colored_name(tag));
+        }
+    }
+    route
+}
+
+fn get_push(g: &mut Game, bots: &Bots, cache: &mut Cache) {
+    let objs: Vec<_> = g.current_objectives().into_iter().map(|obj| {
+        let [obj_c1, obj_c2] = obj.to_chars();
+        format!("\x1B[1m{:?} (\"{}{}\")\x1B[0m", obj, obj_c1, obj_c2)
+    }).collect();
+
+    loop {
+        println!("Player {}, it's your turn. Shift the labyrinth!", colored_name(g.current_player()));
+        if objs.len() == 1 {
+            println!("Your objective is: {}.", objs[0]);
+        } else {
+            println!("Your objectives, in any order, are: {}.", objs.join(", "));
+        }
+        println!();
+        println!("Where do you put the tile?");
+        println!("(for example: enter '1e' to push the tile from the '1' position, oriented towards east)");
+        println!("(type \"hint\" for a hint, \"undo\" or \"redo\" to go back or forth, \"save <file>\" to save the game, \"record <file>\" to write its record)");
+
+        let s = read_line();
+
+        if command(g, &s, bots) {
+            return;
+        }
+
+        // Display hint.
+        if s.trim().eq_ignore_ascii_case("hint") {
+            hint(g, cache);
+            continue;
+        }
+
+        // Check if the push given in input is valid.
+        let entry: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
+        let entry = match entry.parse().ok().and_then(|n| EntryPoint::try_from_num(n, g.board().size())) {
+            Some(entry) => entry,
+            None => {
+                println!("Invalid move!");
+                continue;
+            }
+        };
+
+        let orientation: String = s.trim().chars().filter(|c| !c.is_ascii_digit()).collect();
+        let mut chars = orientation.chars();
+        let orientation = match (chars.next().and_then(Orientation::try_from_char), chars.next()) {
+            (Some(orientation), None) => orientation,
+            _ => {
+                println!("Invalid move!");
+                continue;
+            }
+        };
+
+        match g.apply_push(entry, orientation) {
+            Ok(()) => return,
+            Err(e) => println!("{}", e)
+        }
+    }
+}
+
+// Read the move of a player, returning its path (or nothing if the input
+// was a command).
+fn get_move(g: &mut Game, bots: &Bots) -> Vec<(usize, usize)> {
+    let tag = g.current_player();
+    let (row, col) = g.current_position();
+
+    loop {
+        println!("Player {}, you are in row {}, column {}. Where do you move?", colored_name(g.current_player()), row+1, col+1);
+        println!("(for example: enter '35' to go to row 3, column 5. Or enter 'Sk' to go the Skull)");
+        println!("(type \"undo\" or \"redo\" to go back or forth, \"save <file>\" to save the game, \"record <file>\" to write its record)");
+
+        let s = read_line();
+
+        if command(g, &s, bots) {
+            return vec![];
+        }
+
+        // Check if the move given in input is valid.
+        let mut next_pos = if let Some(obj) = TileContent::try_from_str(&s) {
+            g.board().content_position(obj)
+        } else {
+            None
+        };
+
+        if next_pos.is_none() {
+            let d: Vec<_> = s.chars().filter(|c| c.is_ascii_digit()).collect();
+            if d.len() < 2 || d[0] == '0' || d[1] == '0' {
+                println!("Invalid coordinates.");
+                continue;
+            }
+            next_pos = Some(((d[0].to_digit(10).unwrap()-1) as usize, (d[1].to_digit(10).unwrap()-1) as usize));
+        }
+
+        match g.apply_move(next_pos.unwrap()) {
+            Ok(MoveOutcome::Moved) => {},
+            Ok(MoveOutcome::Collected { objective, remaining }) => {
+                // Not counting going back home.
+                let left = remaining - g.rules().return_home as usize;
+                if left > 1 {
+                    println!("You reached the \x1B[1m{:?}\x1B[0m! You have now {} objectives to go!", objective, left);
+                } else if left == 1 {
+                    println!("You reached the \x1B[1m{:?}\x1B[0m! You have now only {} objective to go!", objective, left);
+                } else {
+                    println!("You reached the \x1B[1m{:?}\x1B[0m! You just have to go back to the start tile (\"{}\") to win!", objective, tag.to_ascii_uppercase());
+                }
+            },
+            Ok(MoveOutcome::Won) => {
+                // Victory!
+                println!("{}", g.draw_to_string());
+                println!("Player {} wins the game! Congratulations!", colored_name(tag));
+            },
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        }
+        // Collecting an object does not change the paths.
+        return g.board().shortest_path((row, col), next_pos.unwrap()).unwrap();
+    }
+}
+
+fn hint(g: &Game, cache: &mut Cache) {
+    let hint = g.hint_with_cache(cache).unwrap();
+    let objs = g.current_objectives();
+    let obj = match objs.as_slice() {
+        [obj] => format!("the {:?}", obj),
+        _ => "any of your objectives".to_string()
+    };
+    let push = Action::Push(hint.push.0, hint.push.1).notation(g.board().size());
+    let (row, col) = hint.cell;
+
+    println!();
+    if let Some(reached) = hint.reached {
+        println!("The {:?} is reachable in one move: push \"{}\" and then move to \"{} {}\".", reached, push, row+1, col+1);
+    } else if hint.probability == 0.0 {
+        println!("You cannot reach {} in one move, nor in two moves.", obj);
+    } else {
+        println!("You cannot reach {} in one move.", obj);
+        println!("You should consider pushing \"{}\" and then moving to \"{} {}\".", push, row+1, col+1);
+        let opponents = if g.num_players() == 2 { "your opponent does" } else { "your opponents do" };
+        if hint.probability < 1.0 {
+            let chance = if hint.probability <= 0.5 { "some chance" } else { "a good chance" };
+            println!("Depending on what {}, you have {} ({:.0}%) to get to {} on the next move.", opponents, chance, 100.0*hint.probability, obj);
+        } else {
+            println!("Irrespective of what {}, you will be able to get to {} on the next move.", opponents, obj);
+        }
     }
+    println!();
 }
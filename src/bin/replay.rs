@@ -0,0 +1,98 @@
+use labyrinth_game::{Action, Game};
+use labyrinth_game::record::Record;
+
+fn colored_name(tag: char) -> String {
+    match tag {
+        'r' => "\x1B[1;31mRed\x1B[0m".to_string(),
+        'b' => "\x1B[1;34mBlue\x1B[0m".to_string(),
+        'y' => "\x1B[1;33mYellow\x1B[0m".to_string(),
+        'g' => "\x1B[1;32mGreen\x1B[0m".to_string(),
+        _ => panic!()
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 {
+        println!("Usage: {} <game record>", args[0]);
+        return;
+    }
+
+    let record = match std::fs::read_to_string(&args[1]).map_err(|e| e.to_string())
+        .and_then(|s| Record::parse(&s).map_err(|e| e.to_string())) {
+        Ok(record) => record,
+        Err(e) => {
+            println!("Cannot read {}: {}", args[1], e);
+            return;
+        }
+    };
+
+    // Play the whole game, then go back to the start: stepping through
+    // the game is just undoing and redoing the actions.
+    let mut g = record.replay().expect("a parsed record is legal");
+    while g.undo().is_some() {}
+
+    let mut step = 0;
+    loop {
+        show(&g, &record, step);
+
+        let mut s = String::new();
+        if std::io::stdin().read_line(&mut s).expect("Failed to read line") == 0 {
+            return;
+        }
+
+        let target = match s.trim() {
+            "" | "n" => step + 1,
+            "p" => step.saturating_sub(1),
+            "f" => 0,
+            "l" => record.actions.len(),
+            "q" => return,
+            t => match t.parse::<usize>() {
+                // Go to the beginning of the given turn.
+                Ok(turn) if turn > 0 => 2*(turn-1),
+                _ => {
+                    println!("Unknown command.");
+                    continue;
+                }
+            }
+        };
+
+        while step < target.min(record.actions.len()) {
+            g.redo();
+            step += 1;
+        }
+        while step > target {
+            g.undo();
+            step -= 1;
+        }
+    }
+}
+
+fn show(g: &Game, record: &Record, step: usize) {
+    println!("{}", g.draw_to_string());
+
+    if step == 0 {
+        println!("Start of the game (seed {}).", record.seed);
+    } else {
+        // Each turn is a push followed by a move.
+        let turn = (step-1) / 2;
+        let player = colored_name(record.players[turn % record.players.len()]);
+        let push = record.actions[2*turn].notation(record.rules.size);
+        match record.actions.get(2*turn+1) {
+            Some(m @ Action::Move(_)) if step == 2*turn+2 => {
+                println!("Turn {}: {} pushed \"{}\" and moved to \"{}\".", turn+1, player, push, m.notation(record.rules.size));
+            },
+            _ => {
+                println!("Turn {}: {} pushed \"{}\".", turn+1, player, push);
+            }
+        }
+    }
+    if g.is_over() {
+        println!("Player {} won the game.", colored_name(g.current_player()));
+    }
+
+    println!();
+    println!("Step {} of {}.", step, record.actions.len());
+    println!("(press enter or 'n' for the next step, 'p' for the previous one, 'f' and 'l' for the first and the last,");
+    println!(" a number to go to that turn, 'q' to quit)");
+}
@@ -1,16 +1,37 @@
-use std::collections::HashSet;
-use std::collections::VecDeque;
+use crate::rules::RuleSet;
+use std::collections::{HashSet, VecDeque};
 use rand::{thread_rng, Rng};
 use rand::seq::SliceRandom;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileKind {
     Straight,
     Corner,
     Junction
 }
 
+impl TileKind {
+    pub fn to_char(self) -> char {
+        match self {
+            TileKind::Straight => 'S',
+            TileKind::Corner => 'C',
+            TileKind::Junction => 'J'
+        }
+    }
+
+    pub fn try_from_char(c: char) -> Option<TileKind> {
+        match c.to_ascii_uppercase() {
+            'S' => Some(TileKind::Straight),
+            'C' => Some(TileKind::Corner),
+            'J' => Some(TileKind::Junction),
+            _ => None
+        }
+    }
+}
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileContent {
     None,
     YellowMarker,
@@ -114,65 +135,100 @@ impl TileContent {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tile {
     pub kind: TileKind,
     pub content: TileContent
 }
 
+// The objects on the fixed tiles, in the order in which they are placed
+// (row by row), and the objects on the movable tiles.
+const FIXED_OBJECTS: [TileContent; 12] = [
+    TileContent::Skull, TileContent::Sword, TileContent::Gold, TileContent::Keys,
+    TileContent::Gem, TileContent::Helm, TileContent::Book, TileContent::Crown,
+    TileContent::Treasure, TileContent::Candelabrum, TileContent::Map, TileContent::Ring
+];
+
+const MOVABLE_OBJECTS: [(TileKind, TileContent); 12] = [
+    (TileKind::Junction, TileContent::Bat), (TileKind::Corner, TileContent::Beetle),
+    (TileKind::Junction, TileContent::Dragon), (TileKind::Corner, TileContent::Moth),
+    (TileKind::Junction, TileContent::Fairy), (TileKind::Corner, TileContent::Owl),
+    (TileKind::Junction, TileContent::Genie), (TileKind::Corner, TileContent::Rat),
+    (TileKind::Junction, TileContent::Ghost), (TileKind::Corner, TileContent::Salamander),
+    (TileKind::Junction, TileContent::Troll), (TileKind::Corner, TileContent::Spider)
+];
+
 impl Tile {
-    fn standard_set() -> Vec<Tile> {
-        vec![
-            Tile { kind: TileKind::Corner, content: TileContent::RedMarker },
-            Tile { kind: TileKind::Corner, content: TileContent::BlueMarker },
-            Tile { kind: TileKind::Corner, content: TileContent::YellowMarker },
-            Tile { kind: TileKind::Corner, content: TileContent::GreenMarker },
-            Tile { kind: TileKind::Junction, content: TileContent::Bat },
-            Tile { kind: TileKind::Corner, content: TileContent::Beetle },
-            Tile { kind: TileKind::Junction, content: TileContent::Book },
-            Tile { kind: TileKind::Junction, content: TileContent::Candelabrum },
-            Tile { kind: TileKind::Junction, content: TileContent::Crown },
-            Tile { kind: TileKind::Junction, content: TileContent::Dragon },
-            Tile { kind: TileKind::Junction, content: TileContent::Fairy },
-            Tile { kind: TileKind::Junction, content: TileContent::Gem },
-            Tile { kind: TileKind::Junction, content: TileContent::Gold },
-            Tile { kind: TileKind::Junction, content: TileContent::Genie },
-            Tile { kind: TileKind::Junction, content: TileContent::Ghost },
-            Tile { kind: TileKind::Junction, content: TileContent::Helm },
-            Tile { kind: TileKind::Junction, content: TileContent::Keys },
-            Tile { kind: TileKind::Junction, content: TileContent::Map },
-            Tile { kind: TileKind::Corner, content: TileContent::Moth },
-            Tile { kind: TileKind::Corner, content: TileContent::Owl },
-            Tile { kind: TileKind::Corner, content: TileContent::Rat },
-            Tile { kind: TileKind::Junction, content: TileContent::Ring },
-            Tile { kind: TileKind::Corner, content: TileContent::Salamander },
-            Tile { kind: TileKind::Corner, content: TileContent::Spider },
-            Tile { kind: TileKind::Junction, content: TileContent::Treasure },
-            Tile { kind: TileKind::Junction, content: TileContent::Troll },
-            Tile { kind: TileKind::Junction, content: TileContent::Skull },
-            Tile { kind: TileKind::Junction, content: TileContent::Sword },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Corner, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-            Tile { kind: TileKind::Straight, content: TileContent::None },
-        ]
+    // The fixed tiles (in even rows and columns) of a board of the given size,
+    // row by row. On a 7x7 board this is the layout of the original game.
+    fn fixed_set(size: usize) -> Vec<(Tile, Orientation)> {
+        let last = size - 1;
+        let num_fixed = (size/2+1) * (size/2+1) - 4;
+        let num_objects = Tile::num_fixed_objects(size);
+        let mut objects = FIXED_OBJECTS.iter();
+        let mut i = 0;
+
+        let mut res = vec![];
+        for row in (0..size).step_by(2) {
+            for col in (0..size).step_by(2) {
+                let corner = |content| Tile { kind: TileKind::Corner, content };
+                res.push(match (row, col) {
+                    (0, 0) => (corner(TileContent::RedMarker), Orientation::East),
+                    (0, c) if c == last => (corner(TileContent::BlueMarker), Orientation::South),
+                    (r, 0) if r == last => (corner(TileContent::YellowMarker), Orientation::North),
+                    (r, c) if r == last && c == last => (corner(TileContent::GreenMarker), Orientation::West),
+                    _ => {
+                        // Spread the objects evenly on the other fixed tiles.
+                        let content = if (i+1)*num_objects/num_fixed > i*num_objects/num_fixed {
+                            *objects.next().unwrap()
+                        } else {
+                            TileContent::None
+                        };
+                        i += 1;
+                        (Tile { kind: TileKind::Junction, content }, Tile::fixed_orientation(size, row, col))
+                    }
+                });
+            }
+        }
+        res
+    }
+
+    // Fixed junctions on the border are closed towards the outside, the
+    // ones inside the board turn around the center.
+    fn fixed_orientation(size: usize, row: usize, col: usize) -> Orientation {
+        let last = size - 1;
+        let (dr, dc) = (row as isize - (last/2) as isize, col as isize - (last/2) as isize);
+
+        match (row, col) {
+            (0, _) => Orientation::North,
+            (r, _) if r == last => Orientation::South,
+            (_, 0) => Orientation::West,
+            (_, c) if c == last => Orientation::East,
+            _ if dr <= 0 && dc < 0 => Orientation::West,
+            _ if dr < 0 && dc >= 0 => Orientation::North,
+            _ if dr >= 0 && dc > 0 => Orientation::East,
+            _ if dr > 0 && dc <= 0 => Orientation::South,
+            _ => Orientation::North
+        }
+    }
+
+    fn num_fixed_objects(size: usize) -> usize {
+        ((size/2+1) * (size/2+1) - 4).min(FIXED_OBJECTS.len())
+    }
+
+    // The movable tiles of a board of the given size, including the extra
+    // tile: as many objects as on the fixed tiles, and empty corners and
+    // straight tiles in the same proportion as in the original game.
+    fn movable_set(size: usize) -> Vec<Tile> {
+        let num_movable = size*size + 1 - (size/2+1) * (size/2+1);
+        let num_objects = Tile::num_fixed_objects(size);
+        let num_empty = num_movable - num_objects;
+        let num_straight = num_empty * 6 / 11;
+
+        let mut res: Vec<_> = MOVABLE_OBJECTS[..num_objects].iter().map(|&(kind, content)| Tile { kind, content }).collect();
+        res.resize(num_objects + num_straight, Tile { kind: TileKind::Straight, content: TileContent::None });
+        res.resize(num_movable, Tile { kind: TileKind::Corner, content: TileContent::None });
+        res
     }
 
     // Outputs whether the tile, in a certain orientation, connects
@@ -215,6 +271,7 @@ impl Tile {
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Orientation {
     North,
     East,
@@ -223,8 +280,7 @@ pub enum Orientation {
 }
 
 impl Orientation {
-    fn random() -> Orientation {
-        let mut rng = thread_rng();
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Orientation {
         match rng.gen_range(0, 4) {
             0 => Orientation::North,
             1 => Orientation::East,
@@ -233,126 +289,296 @@ impl Orientation {
             _ => unreachable!()
         }
     }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Orientation::North => 'n',
+            Orientation::East => 'e',
+            Orientation::South => 's',
+            Orientation::West => 'w'
+        }
+    }
+
+    pub fn try_from_char(c: char) -> Option<Orientation> {
+        match c.to_ascii_lowercase() {
+            'n' => Some(Orientation::North),
+            'e' => Some(Orientation::East),
+            's' => Some(Orientation::South),
+            'w' => Some(Orientation::West),
+            _ => None
+        }
+    }
 }
 
+// Where the extra tile is pushed into the board: from one of the sides,
+// into the column (North, South) or row (East, West) with the given index.
+// Only the odd rows and columns can be moved.
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EntryPoint {
-    NorthLeft,
-    NorthCenter,
-    NorthRight,
-    EastUp,
-    EastCenter,
-    EastDown,
-    SouthLeft,
-    SouthCenter,
-    SouthRight,
-    WestUp,
-    WestCenter,
-    WestDown
+    North(usize),
+    East(usize),
+    South(usize),
+    West(usize)
+}
+
+impl EntryPoint {
+    // Entry points are numbered clockwise, starting from the top left.
+    // On a 7x7 board they go from 1 to 12.
+    pub fn to_num(self, size: usize) -> u8 {
+        let k = size/2;
+        let n = match self {
+            EntryPoint::North(col) => col/2 + 1,
+            EntryPoint::East(row) => k + row/2 + 1,
+            EntryPoint::South(col) => 2*k + (size-col)/2,
+            EntryPoint::West(row) => 3*k + (size-row)/2
+        };
+        n as u8
+    }
+
+    pub fn try_from_num(n: u8, size: usize) -> Option<EntryPoint> {
+        let k = size/2;
+        let n = n as usize;
+        if n == 0 {
+            return None;
+        }
+        match (n-1) / k {
+            0 => Some(EntryPoint::North(2*n-1)),
+            1 => Some(EntryPoint::East(2*(n-k)-1)),
+            2 => Some(EntryPoint::South(size - 2*(n-2*k))),
+            3 => Some(EntryPoint::West(size - 2*(n-3*k))),
+            _ => None
+        }
+    }
+
+    // The entry point on the other end of the same row or column.
+    pub fn opposite(self) -> EntryPoint {
+        match self {
+            EntryPoint::North(col) => EntryPoint::South(col),
+            EntryPoint::East(row) => EntryPoint::West(row),
+            EntryPoint::South(col) => EntryPoint::North(col),
+            EntryPoint::West(row) => EntryPoint::East(row)
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+// The supported board sizes: odd sizes, so that the border and every other
+// row and column are fixed, and up to 9 so that coordinates are single digits.
+pub const MIN_SIZE: usize = 5;
+pub const MAX_SIZE: usize = 9;
+
+// The tiles are stored in a fixed-size array, large enough for any size,
+// so that boards can be copied without allocating. Only the first size*size
+// cells are used.
+const MAX_CELLS: usize = MAX_SIZE * MAX_SIZE;
+const UNUSED_CELL: (Tile, Orientation) = (Tile { kind: TileKind::Straight, content: TileContent::None }, Orientation::North);
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "BoardData", try_from = "BoardData"))]
 pub struct Board {
+    size: usize,
+    tiles: [(Tile, Orientation); MAX_CELLS],
+    extra_tile: Tile,
+    extra_pos: Option<EntryPoint>
+}
+
+// Boards are serialized with only the cells that are used.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BoardData {
+    size: usize,
     tiles: Vec<(Tile, Orientation)>,
     extra_tile: Tile,
     extra_pos: Option<EntryPoint>
 }
 
+#[cfg(feature = "serde")]
+impl From<Board> for BoardData {
+    fn from(b: Board) -> Self {
+        BoardData {
+            size: b.size,
+            tiles: b.cells().to_vec(),
+            extra_tile: b.extra_tile,
+            extra_pos: b.extra_pos
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<BoardData> for Board {
+    type Error = String;
+
+    fn try_from(b: BoardData) -> Result<Self, Self::Error> {
+        if !Board::is_valid_size(b.size) || b.tiles.len() != b.size*b.size {
+            return Err(format!("invalid board of size {} with {} tiles", b.size, b.tiles.len()));
+        }
+        let board = Board::from_tiles(b.size, b.tiles, b.extra_tile, b.extra_pos);
+        if b.extra_pos.is_some_and(|e| !board.is_valid_entry(e)) {
+            return Err("invalid position of the extra tile".to_string());
+        }
+        Ok(board)
+    }
+}
+
 impl Board {
     pub fn new() -> Board {
-        // Generate tiles in a random order.
-        let mut t = Tile::standard_set();
-        t.shuffle(&mut thread_rng());
-
-        // Generate random orientations.
-        let mut o = vec![];
-        o.resize_with(49, Orientation::random);
-
-        // Setup fixed elements.
-        Board::place(&mut t, &mut o, TileContent::RedMarker, 0, Orientation::East);
-        Board::place(&mut t, &mut o, TileContent::Skull, 2, Orientation::North);
-        Board::place(&mut t, &mut o, TileContent::Sword, 4, Orientation::North);
-        Board::place(&mut t, &mut o, TileContent::BlueMarker, 6, Orientation::South);
-        Board::place(&mut t, &mut o, TileContent::Gold, 14, Orientation::West);
-        Board::place(&mut t, &mut o, TileContent::Keys, 16, Orientation::West);
-        Board::place(&mut t, &mut o, TileContent::Gem, 18, Orientation::North);
-        Board::place(&mut t, &mut o, TileContent::Helm, 20, Orientation::East);
-        Board::place(&mut t, &mut o, TileContent::Book, 28, Orientation::West);
-        Board::place(&mut t, &mut o, TileContent::Crown, 30, Orientation::South);
-        Board::place(&mut t, &mut o, TileContent::Treasure, 32, Orientation::East);
-        Board::place(&mut t, &mut o, TileContent::Candelabrum, 34, Orientation::East);
-        Board::place(&mut t, &mut o, TileContent::YellowMarker, 42, Orientation::North);
-        Board::place(&mut t, &mut o, TileContent::Map, 44, Orientation::South);
-        Board::place(&mut t, &mut o, TileContent::Ring, 46, Orientation::South);
-        Board::place(&mut t, &mut o, TileContent::GreenMarker, 48, Orientation::West);
-
-        // Fill in the board.
-        let mut tile_it = t.into_iter();
-        let mut orient_it = o.into_iter();
-
-        let mut tiles = vec![];
-        tiles.resize_with(49, || (tile_it.next().unwrap(), orient_it.next().unwrap()));
-        let extra_tile = tile_it.next().unwrap();
-
-        Board { tiles, extra_tile, extra_pos: None }
+        Board::with_rng(&mut thread_rng())
+    }
+
+    // Generate a board drawing all the random choices from the given
+    // generator, so that the same generator state gives the same board.
+    pub fn with_rng<R: Rng + ?Sized>(rng: &mut R) -> Board {
+        Board::with_size(7, rng)
+    }
+
+    pub fn with_size<R: Rng + ?Sized>(size: usize, rng: &mut R) -> Board {
+        assert!(Board::is_valid_size(size));
+
+        // Generate the movable tiles in a random order.
+        let mut movable = Tile::movable_set(size);
+        movable.shuffle(rng);
+
+        // Fill in the board, with random orientations for the movable tiles.
+        let mut fixed_it = Tile::fixed_set(size).into_iter();
+        let mut movable_it = movable.into_iter();
+
+        let mut tiles = [UNUSED_CELL; MAX_CELLS];
+        for row in 0..size {
+            for col in 0..size {
+                tiles[row*size+col] = if row % 2 == 0 && col % 2 == 0 {
+                    fixed_it.next().unwrap()
+                } else {
+                    (movable_it.next().unwrap(), Orientation::random(rng))
+                };
+            }
+        }
+        let extra_tile = movable_it.next().unwrap();
+
+        Board { size, tiles, extra_tile, extra_pos: None }
+    }
+
+    pub fn is_valid_size(size: usize) -> bool {
+        size % 2 == 1 && (MIN_SIZE..=MAX_SIZE).contains(&size)
+    }
+
+    // Build a board from its tiles, listed row by row.
+    pub(crate) fn from_tiles(size: usize, tiles: Vec<(Tile, Orientation)>, extra_tile: Tile, extra_pos: Option<EntryPoint>) -> Board {
+        assert!(Board::is_valid_size(size));
+        assert_eq!(tiles.len(), size*size);
+        let mut cells = [UNUSED_CELL; MAX_CELLS];
+        cells[..size*size].copy_from_slice(&tiles);
+        Board { size, tiles: cells, extra_tile, extra_pos }
+    }
+
+    // The tiles that are used, row by row.
+    fn cells(&self) -> &[(Tile, Orientation)] {
+        &self.tiles[..self.size*self.size]
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // All the entry points, clockwise from the top left.
+    pub fn entry_points(&self) -> Vec<EntryPoint> {
+        let lines = (1..self.size).step_by(2);
+        lines.clone().map(EntryPoint::North)
+            .chain(lines.clone().map(EntryPoint::East))
+            .chain(lines.clone().rev().map(EntryPoint::South))
+            .chain(lines.rev().map(EntryPoint::West))
+            .collect()
+    }
+
+    pub fn is_valid_entry(&self, entry: EntryPoint) -> bool {
+        match entry {
+            EntryPoint::North(i) | EntryPoint::East(i) | EntryPoint::South(i) | EntryPoint::West(i) => i % 2 == 1 && i < self.size
+        }
+    }
+
+    // Whether pushing at the given entry point would undo the previous push,
+    // which is not allowed by the standard rules.
+    pub fn is_reverse_push(&self, entry: EntryPoint) -> bool {
+        Some(entry) == self.extra_pos
+    }
+
+    // All the pushes that the rules allow, with the extra tile in each of
+    // the four orientations.
+    pub fn legal_pushes(&self, rules: &RuleSet) -> Vec<(EntryPoint, Orientation)> {
+        let orientations = [Orientation::North, Orientation::East, Orientation::South, Orientation::West];
+        self.pushes_with(&orientations, rules)
+    }
+
+    // The pushes that lead to different boards: a straight tile looks the
+    // same in opposite orientations, so only two of them are kept.
+    pub fn distinct_legal_pushes(&self, rules: &RuleSet) -> Vec<(EntryPoint, Orientation)> {
+        match self.extra_tile.kind {
+            TileKind::Straight => self.pushes_with(&[Orientation::North, Orientation::East], rules),
+            _ => self.legal_pushes(rules)
+        }
+    }
+
+    fn pushes_with(&self, orientations: &[Orientation], rules: &RuleSet) -> Vec<(EntryPoint, Orientation)> {
+        self.entry_points().into_iter()
+            .filter(|e| rules.reverse_push || !self.is_reverse_push(*e))
+            .flat_map(|e| orientations.iter().map(move |o| (e, *o)))
+            .collect()
     }
 
     // Make a move by pushing the extra tile into the board, at a given
     // entry point and with a given orientation.
-    // In addition, update the positions given in input (with wrapping)
-    pub fn push_tile(&mut self, entry: EntryPoint, orientation: Orientation, positions: &mut [&mut (usize, usize)]) {
-        let (out_tile, out_pos) = match entry {
-            EntryPoint::NorthLeft   => (self.push_north(1, orientation), EntryPoint::SouthLeft),
-            EntryPoint::NorthCenter => (self.push_north(3, orientation), EntryPoint::SouthCenter),
-            EntryPoint::NorthRight  => (self.push_north(5, orientation), EntryPoint::SouthRight),
-            EntryPoint::EastUp      => (self.push_east(1, orientation), EntryPoint::WestUp),
-            EntryPoint::EastCenter  => (self.push_east(3, orientation), EntryPoint::WestCenter),
-            EntryPoint::EastDown    => (self.push_east(5, orientation), EntryPoint::WestDown),
-            EntryPoint::SouthLeft   => (self.push_south(1, orientation), EntryPoint::NorthLeft),
-            EntryPoint::SouthCenter => (self.push_south(3, orientation), EntryPoint::NorthCenter),
-            EntryPoint::SouthRight  => (self.push_south(5, orientation), EntryPoint::NorthRight),
-            EntryPoint::WestUp      => (self.push_west(1, orientation), EntryPoint::EastUp),
-            EntryPoint::WestCenter  => (self.push_west(3, orientation), EntryPoint::EastCenter),
-            EntryPoint::WestDown    => (self.push_west(5, orientation), EntryPoint::EastDown)
+    // In addition, update the positions given in input (with wrapping, or
+    // staying at the edge of the board if the rules say so).
+    pub fn push_tile(&mut self, entry: EntryPoint, orientation: Orientation, positions: &mut [&mut (usize, usize)], rules: &RuleSet) {
+        assert!(self.is_valid_entry(entry));
+
+        let out_tile = match entry {
+            EntryPoint::North(col) => self.push_north(col, orientation),
+            EntryPoint::East(row) => self.push_east(row, orientation),
+            EntryPoint::South(col) => self.push_south(col, orientation),
+            EntryPoint::West(row) => self.push_west(row, orientation)
         };
         self.extra_tile = out_tile;
-        self.extra_pos = Some(out_pos);
+        self.extra_pos = Some(entry.opposite());
 
         // Update positions.
+        let (n, wrap) = (self.size, rules.wrap_around);
+        let forward = |x: usize| if x + 1 < n { x + 1 } else if wrap { 0 } else { x };
+        let backward = |x: usize| if x > 0 { x - 1 } else if wrap { n - 1 } else { x };
         for pos in positions {
-            match (pos.0, entry) {
-                (1, EntryPoint::EastUp) => { pos.1 = (pos.1 + 6) % 7 },
-                (1, EntryPoint::WestUp) => { pos.1 = (pos.1 + 1) % 7 },
-                (3, EntryPoint::EastCenter) => { pos.1 = (pos.1 + 6) % 7 },
-                (3, EntryPoint::WestCenter) => { pos.1 = (pos.1 + 1) % 7 },
-                (5, EntryPoint::EastDown) => { pos.1 = (pos.1 + 6) % 7 },
-                (5, EntryPoint::WestDown) => { pos.1 = (pos.1 + 1) % 7 },
-                _ => {}
-            }
-            match (pos.1, entry) {
-                (1, EntryPoint::NorthLeft) => { pos.0 = (pos.0 + 1) % 7 },
-                (1, EntryPoint::SouthLeft) => { pos.0 = (pos.0 + 6) % 7 },
-                (3, EntryPoint::NorthCenter) => { pos.0 = (pos.0 + 1) % 7 },
-                (3, EntryPoint::SouthCenter) => { pos.0 = (pos.0 + 6) % 7 },
-                (5, EntryPoint::NorthRight) => { pos.0 = (pos.0 + 1) % 7 },
-                (5, EntryPoint::SouthRight) => { pos.0 = (pos.0 + 6) % 7 },
+            match entry {
+                EntryPoint::North(col) if pos.1 == col => { pos.0 = forward(pos.0) },
+                EntryPoint::East(row) if pos.0 == row => { pos.1 = backward(pos.1) },
+                EntryPoint::South(col) if pos.1 == col => { pos.0 = backward(pos.0) },
+                EntryPoint::West(row) if pos.0 == row => { pos.1 = forward(pos.1) },
                 _ => {}
             }
         }
     }
 
+    pub fn tile_at(&self, row: usize, col: usize) -> (Tile, Orientation) {
+        assert!(row < self.size);
+        assert!(col < self.size);
+        self.tiles[row*self.size+col]
+    }
+
     pub fn content_at(&self, row: usize, col: usize) -> TileContent {
-        assert!(row < 7);
-        assert!(col < 7);
-        self.tiles[row*7+col].0.content
+        self.tile_at(row, col).0.content
     }
 
     pub fn content_position(&self, content: TileContent) -> Option<(usize, usize)> {
-        if let Some(i) = self.tiles.iter().position(|x| x.0.content == content) {
-            Some((i / 7, i % 7))
-        } else {
-            None
-        }
+        self.cells().iter().position(|x| x.0.content == content).map(|i| (i / self.size, i % self.size))
+    }
+
+    // The objects that are on the board (including the extra tile), in
+    // the order in which they are declared.
+    pub fn objects(&self) -> Vec<TileContent> {
+        let mut res: Vec<_> = self.cells().iter().map(|x| x.0.content)
+            .chain(std::iter::once(self.extra_tile.content))
+            .filter(|c| FIXED_OBJECTS.contains(c) || MOVABLE_OBJECTS.iter().any(|x| x.1 == *c))
+            .collect();
+        res.sort_by_key(|c| *c as usize);
+        res
     }
 
     pub fn extra_tile(&self) -> Tile {
@@ -364,149 +590,243 @@ impl Board {
     }
 
     pub fn remove_content(&mut self, content: TileContent) {
-        if let Some(i) = self.tiles.iter().position(|x| x.0.content == content) {
+        if let Some(i) = self.cells().iter().position(|x| x.0.content == content) {
             self.tiles[i].0.content = TileContent::None;
         }
     }
 
     fn push_north(&mut self, col: usize, orientation: Orientation) -> Tile {
         let mut temp = (self.extra_tile, orientation);
-        for row in 0..7 {
-            std::mem::swap(self.tiles.get_mut(row*7+col).unwrap(), &mut temp);
+        for row in 0..self.size {
+            std::mem::swap(self.tiles.get_mut(row*self.size+col).unwrap(), &mut temp);
         }
         temp.0
     }
 
     fn push_east(&mut self, row: usize, orientation: Orientation) -> Tile  {
         let mut temp = (self.extra_tile, orientation);
-        for col in (0..7).rev() {
-            std::mem::swap(self.tiles.get_mut(row*7+col).unwrap(), &mut temp);
+        for col in (0..self.size).rev() {
+            std::mem::swap(self.tiles.get_mut(row*self.size+col).unwrap(), &mut temp);
         }
         temp.0
     }
 
     fn push_south(&mut self, col: usize, orientation: Orientation) -> Tile  {
         let mut temp = (self.extra_tile, orientation);
-        for row in (0..7).rev() {
-            std::mem::swap(self.tiles.get_mut(row*7+col).unwrap(), &mut temp);
+        for row in (0..self.size).rev() {
+            std::mem::swap(self.tiles.get_mut(row*self.size+col).unwrap(), &mut temp);
         }
         temp.0
     }
 
     fn push_west(&mut self, row: usize, orientation: Orientation) -> Tile  {
         let mut temp = (self.extra_tile, orientation);
-        for col in 0..7 {
-            std::mem::swap(self.tiles.get_mut(row*7+col).unwrap(), &mut temp);
+        for col in 0..self.size {
+            std::mem::swap(self.tiles.get_mut(row*self.size+col).unwrap(), &mut temp);
         }
         temp.0
     }
 
-    fn place(t: &mut [Tile], o: &mut [Orientation], object: TileContent, position: usize, orientation: Orientation) {
-        t.swap(t.iter().position(|x| x.content == object).unwrap(), position);
-        o[position] = orientation;
-    }
-
-    pub fn to_ascii_29x56(&self) -> [[char; 56]; 29] {
-        let mut res = [[' '; 56]; 29];
-
-        // Draw markers on the perimeter.
-        res[2][16] = '1'; res[2][28] = '2'; res[2][40] = '3';
-        res[3][15] = '\\'; res[3][27] = '\\'; res[3][39] = '\\';
-        res[3][16] = '/'; res[3][28] = '/'; res[3][40] = '/';
-        res[8][49] = '<'; res[14][49] = '<'; res[20][49] = '<';
-        res[8][50] = '4'; res[14][50] = '5'; res[20][50] = '6';
-        res[25][15] = '/'; res[25][27] = '/'; res[25][39] = '/';
-        res[25][16] = '\\'; res[25][28] = '\\'; res[25][40] = '\\';
-        res[26][16] = '9'; res[26][28] = '8'; res[26][40] = '7';
-        res[8][6] = '>'; res[14][6] = '>'; res[20][6] = '>';
-        res[8][4] = '1'; res[14][4] = '1'; res[20][4] = '1';
-        res[8][5] = '2'; res[14][5] = '1'; res[20][5] = '0';
+    // Draw the board on a grid of characters: each tile takes 3 rows and 6
+    // columns, with a margin for the extra tile and the entry points all
+    // around. A 7x7 board takes 29 rows and 56 columns.
+    pub fn to_ascii(&self) -> Vec<Vec<char>> {
+        let n = self.size;
+        let mut res = vec![vec![' '; 6*(n+2)+2]; 3*(n+2)+2];
+
+        // Draw markers on the perimeter. Labels are right-aligned on the
+        // left side and on the arrows, left-aligned on the right side.
+        for entry in self.entry_points() {
+            let label: Vec<_> = entry.to_num(n).to_string().chars().collect();
+            let (row, col) = match entry {
+                EntryPoint::North(c) => {
+                    res[3][6*(1+c)+3] = '\\'; res[3][6*(1+c)+4] = '/';
+                    (2, 6*(1+c)+5-label.len())
+                },
+                EntryPoint::East(r) => {
+                    res[3*(1+r)+2][6*(n+1)+1] = '<';
+                    (3*(1+r)+2, 6*(n+1)+2)
+                },
+                EntryPoint::South(c) => {
+                    res[3*(n+1)+1][6*(1+c)+3] = '/'; res[3*(n+1)+1][6*(1+c)+4] = '\\';
+                    (3*(n+1)+2, 6*(1+c)+5-label.len())
+                },
+                EntryPoint::West(r) => {
+                    res[3*(1+r)+2][6] = '>';
+                    (3*(1+r)+2, 6-label.len())
+                }
+            };
+            res[row][col..col+label.len()].copy_from_slice(&label);
+        }
 
         // Draw the tiles on the board.
-        for r in 0..7 {
-            for c in 0..7 {
-                Board::put_ascii_tile(&mut res, 3*(1+r)+1, 6*(1+c)+1, self.tiles[r*7+c].0, self.tiles[r*7+c].1);
+        for r in 0..n {
+            for c in 0..n {
+                let (tile, orientation) = self.tiles[r*n+c];
+                Board::put_ascii_tile(&mut res, 3*(1+r)+1, 6*(1+c)+1, tile, orientation);
             }
         }
 
         // Draw the extra tile.
         let (offset_row, offset_col) = match self.extra_pos {
             None => (0, 0),
-            Some(EntryPoint::NorthLeft) => (0, 13),
-            Some(EntryPoint::NorthCenter) => (0, 25),
-            Some(EntryPoint::NorthRight) => (0, 37),
-            Some(EntryPoint::EastUp) => (7, 50),
-            Some(EntryPoint::EastCenter) => (13, 50),
-            Some(EntryPoint::EastDown) => (19, 50),
-            Some(EntryPoint::SouthLeft) => (25, 13),
-            Some(EntryPoint::SouthCenter) => (25, 25),
-            Some(EntryPoint::SouthRight) => (25, 37),
-            Some(EntryPoint::WestUp) => (7, 0),
-            Some(EntryPoint::WestCenter) => (13, 0),
-            Some(EntryPoint::WestDown) => (19, 0)
+            Some(EntryPoint::North(c)) => (0, 6*(1+c)+1),
+            Some(EntryPoint::East(r)) => (3*(1+r)+1, 6*(n+1)+2),
+            Some(EntryPoint::South(c)) => (3*(n+1)+1, 6*(1+c)+1),
+            Some(EntryPoint::West(r)) => (3*(1+r)+1, 0)
         };
         Board::put_ascii_tile(&mut res, offset_row, offset_col, self.extra_tile, Orientation::North);
         res
     }
 
-    fn put_ascii_tile(grid: &mut [[char; 56]; 29], grid_row: usize, grid_col: usize, tile: Tile, orientation: Orientation) {
+    // Draw a path (as returned by shortest_path) on the output of to_ascii,
+    // with dots through the open sides of the tiles.
+    pub fn put_ascii_path(&self, grid: &mut [Vec<char>], path: &[(usize, usize)]) {
+        let origin = |(r, c): (usize, usize)| (3*(1+r)+1, 6*(1+c)+1);
+
+        for &(r, c) in path {
+            let (row, col) = origin((r, c));
+            if self.content_at(r, c) == TileContent::None {
+                grid[row+1][col+2..col+4].copy_from_slice(&['\u{b7}'; 2]);
+            }
+        }
+        for step in path.windows(2) {
+            // Go from the first cell to the second one on the east or south.
+            let (a, b) = if step[0] < step[1] { (step[0], step[1]) } else { (step[1], step[0]) };
+            let ((row_a, col_a), (row_b, col_b)) = (origin(a), origin(b));
+            if a.0 == b.0 {
+                grid[row_a+1][col_a+4..col_a+6].copy_from_slice(&['\u{b7}'; 2]);
+                grid[row_b+1][col_b..col_b+2].copy_from_slice(&['\u{b7}'; 2]);
+            } else {
+                grid[row_a+2][col_a+2..col_a+4].copy_from_slice(&['\u{b7}'; 2]);
+                grid[row_b][col_b+2..col_b+4].copy_from_slice(&['\u{b7}'; 2]);
+            }
+        }
+    }
+
+    fn put_ascii_tile(grid: &mut [Vec<char>], grid_row: usize, grid_col: usize, tile: Tile, orientation: Orientation) {
         let tile_ascii = tile.to_ascii_3x6(orientation);
         for r in 0..3 {
             grid[grid_row+r][grid_col..grid_col+6].copy_from_slice(&tile_ascii[r]);
         }
     }
 
-    // Find locations reachable from a given position.
+    // The connections between neighbouring cells, as bit masks with one bit
+    // per cell (row by row): a bit is set in the first mask if the cell is
+    // connected to the cell on its east, and in the second if it is connected
+    // to the cell on its south. The 81 cells of a 9x9 board need 128 bits.
+    fn links(&self) -> (u128, u128) {
+        let n = self.size;
+        let (mut north, mut east, mut south, mut west) = (0u128, 0u128, 0u128, 0u128);
+        for (i, (tile, orientation)) in self.cells().iter().enumerate() {
+            let (no, ea, so, we) = tile.connections(*orientation);
+            north |= (no as u128) << i;
+            east |= (ea as u128) << i;
+            south |= (so as u128) << i;
+            west |= (we as u128) << i;
+        }
+
+        let last_col = (0..n).fold(0u128, |m, r| m | 1 << (r*n + n-1));
+        (east & (west >> 1) & !last_col, south & (north >> n))
+    }
+
+    // The cells reachable from a given position, as a bit mask with one bit
+    // per cell (row by row).
+    pub fn reachable_mask(&self, row: usize, col: usize) -> u128 {
+        let n = self.size;
+        let (east, south) = self.links();
+        let (west, north) = (east << 1, south << n);
+
+        // Flood fill: extend the reachable cells in all directions at once,
+        // until nothing changes.
+        let mut reachable = 1u128 << (row*n + col);
+        loop {
+            let next = reachable
+                | (reachable & east) << 1
+                | (reachable & west) >> 1
+                | (reachable & south) << n
+                | (reachable & north) >> n;
+            if next == reachable {
+                return reachable;
+            }
+            reachable = next;
+        }
+    }
+
+    // The cells of a mask returned by reachable_mask.
+    pub fn mask_cells(&self, mask: u128) -> impl Iterator<Item = (usize, usize)> {
+        let n = self.size;
+        (0..n*n).filter(move |i| mask & 1 << i != 0).map(move |i| (i / n, i % n))
+    }
+
     pub fn reachable_from(&self, row: usize, col: usize) -> HashSet<(usize, usize)> {
-        let mut reachable = HashSet::new();
+        self.mask_cells(self.reachable_mask(row, col)).collect()
+    }
+
+    // The shortest path between two cells, starting with the first and ending
+    // with the second, or None if the second cannot be reached from the first.
+    pub fn shortest_path(&self, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let n = self.size;
+        let (east, south) = self.links();
+        let (start, end) = (from.0*n + from.1, to.0*n + to.1);
+
+        // Breadth-first search, remembering where each cell was reached from.
+        let mut previous = vec![None; n*n];
         let mut frontier = VecDeque::new();
+        previous[start] = Some(start);
+        frontier.push_back(start);
 
-        frontier.push_back((row, col));
-        reachable.insert((row, col));
-
-        while let Some((r, c)) = frontier.pop_front() {
-            let (ti, or) = self.tiles[r*7+c];
-            let (n, e, s, w) = ti.connections(or);
-            if n && r > 0 {
-                let (ti, or) = self.tiles[(r-1)*7+c];
-                let (_, _, s, _) = ti.connections(or);
-                if s && !reachable.contains(&(r-1, c)) {
-                    frontier.push_back((r-1, c));
-                    reachable.insert((r-1, c));
-                }
+        while let Some(i) = frontier.pop_front() {
+            if i == end {
+                break;
             }
-            if e && c < 6 {
-                let (ti, or) = self.tiles[r*7+(c+1)];
-                let (_, _, _, w) = ti.connections(or);
-                if w && !reachable.contains(&(r, c+1)) {
-                    frontier.push_back((r, c+1));
-                    reachable.insert((r, c+1));
-                }
-            }
-            if s && r < 6 {
-                let (ti, or) = self.tiles[(r+1)*7+c];
-                let (n, _, _, _) = ti.connections(or);
-                if n && !reachable.contains(&(r+1, c)) {
-                    frontier.push_back((r+1, c));
-                    reachable.insert((r+1, c));
-                }
-            }
-            if w && c > 0 {
-                let (ti, or) = self.tiles[r*7+(c-1)];
-                let (_, e, _, _) = ti.connections(or);
-                if e && !reachable.contains(&(r, c-1)) {
-                    frontier.push_back((r, c-1));
-                    reachable.insert((r, c-1));
+            let neighbours = [
+                Some(i+1).filter(|_| east & 1 << i != 0),
+                Some(i+n).filter(|_| south & 1 << i != 0),
+                i.checked_sub(1).filter(|j| east & 1 << j != 0),
+                i.checked_sub(n).filter(|j| south & 1 << j != 0)
+            ];
+            for j in neighbours.iter().flatten() {
+                if previous[*j].is_none() {
+                    previous[*j] = Some(i);
+                    frontier.push_back(*j);
                 }
             }
         }
-        reachable
+
+        previous[end]?;
+        let mut path = vec![to];
+        let mut i = end;
+        while i != start {
+            i = previous[i].unwrap();
+            path.push((i / n, i % n));
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // A hash of the board (tiles, orientations, extra tile and its position),
+    // computed as in Zobrist hashing: each tile in each cell has a fixed
+    // pseudo-random key, and the keys are combined with xor. The keys do not
+    // depend on the run, so the hash is stable.
+    pub fn zobrist_hash(&self) -> u64 {
+        let tile_code = |tile: Tile, orientation: Orientation| {
+            ((tile.kind as u64 * 4 + orientation as u64) << 8) | tile.content as u64
+        };
+
+        let mut hash = splitmix64(self.size as u64);
+        for (i, (tile, orientation)) in self.cells().iter().enumerate() {
+            hash ^= splitmix64(1 << 32 | (i as u64) << 16 | tile_code(*tile, *orientation));
+        }
+        hash ^= splitmix64(2 << 32 | tile_code(self.extra_tile, Orientation::North));
+        if let Some(entry) = self.extra_pos {
+            hash ^= splitmix64(3 << 32 | entry.to_num(self.size) as u64);
+        }
+        hash
     }
 
     pub fn objects_reachable_from(&self, row: usize, col: usize) -> HashSet<TileContent> {
-        let reachable_pos = self.reachable_from(row, col);
-        reachable_pos
-            .into_iter()
+        self.mask_cells(self.reachable_mask(row, col))
             .map(|(row, col)| self.content_at(row, col))
             .filter(|c| c != &TileContent::None)
             .collect()
@@ -518,3 +838,93 @@ impl Default for Board {
         Self::new()
     }
 }
+
+// A fast and well distributed mixing function, used for the hash keys.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+
+    // The cells reachable from a position, by a plain breadth-first search.
+    fn bfs(board: &Board, row: usize, col: usize) -> HashSet<(usize, usize)> {
+        let n = board.size();
+        let connections = |(r, c): (usize, usize)| {
+            let (tile, orientation) = board.tile_at(r, c);
+            tile.connections(orientation)
+        };
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert((row, col));
+        queue.push_back((row, col));
+        while let Some((r, c)) = queue.pop_front() {
+            let (no, ea, so, we) = connections((r, c));
+            let mut next = vec![];
+            if no && r > 0 && connections((r-1, c)).2 { next.push((r-1, c)); }
+            if ea && c+1 < n && connections((r, c+1)).3 { next.push((r, c+1)); }
+            if so && r+1 < n && connections((r+1, c)).0 { next.push((r+1, c)); }
+            if we && c > 0 && connections((r, c-1)).1 { next.push((r, c-1)); }
+            for cell in next {
+                if visited.insert(cell) {
+                    queue.push_back(cell);
+                }
+            }
+        }
+        visited
+    }
+
+    #[test]
+    fn reachable_mask_matches_bfs() {
+        for &size in &[5, 7, 9] {
+            for seed in 0..10 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut board = Board::with_size(size, &mut rng);
+                for _ in 0..5 {
+                    for row in 0..size {
+                        for col in 0..size {
+                            assert_eq!(board.reachable_from(row, col), bfs(&board, row, col), "size {}, seed {}", size, seed);
+                        }
+                    }
+                    let (entry, orientation) = *board.legal_pushes(&RuleSet::default()).choose(&mut rng).unwrap();
+                    board.push_tile(entry, orientation, &mut [], &RuleSet::default());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zobrist_hash_changes() {
+        let rules = RuleSet::default();
+        for &size in &[5, 7, 9] {
+            let board = Board::with_size(size, &mut StdRng::seed_from_u64(1));
+            let hash = board.zobrist_hash();
+            assert_eq!(Board::from_tiles(size, board.cells().to_vec(), board.extra_tile, None).zobrist_hash(), hash);
+
+            for (entry, orientation) in board.legal_pushes(&rules) {
+                let mut pushed = board;
+                pushed.push_tile(entry, orientation, &mut [], &rules);
+                assert_ne!(pushed.zobrist_hash(), hash, "size {}, push {:?}", size, entry);
+
+                // Only the position of the extra tile differs.
+                let moved = Board::from_tiles(size, pushed.cells().to_vec(), pushed.extra_tile, None);
+                assert_ne!(moved.zobrist_hash(), pushed.zobrist_hash());
+            }
+
+            // The objects on the board, not on the extra tile.
+            for obj in board.objects().into_iter().filter(|o| board.content_position(*o).is_some()) {
+                let mut removed = board;
+                removed.remove_content(obj);
+                assert_ne!(removed.zobrist_hash(), hash, "size {}, object {:?}", size, obj);
+            }
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use crate::analyzer::*;
+use crate::analyzer::{self, Cache, Hint};
 use crate::board::{TileContent, Board, EntryPoint, Orientation};
-use rand::thread_rng;
+use crate::rules::RuleSet;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use std::fmt;
 
-#[derive(Debug)]
+mod save;
+mod view;
+pub use save::LoadError;
+pub use view::PlayerView;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Player {
     tag: char,
     pos: (usize, usize),
@@ -11,330 +20,507 @@ struct Player {
 }
 
 impl Player {
-    fn to_colored_str(&self) -> String {
-        match self.tag {
-            'r' => "\x1B[1;31mRed\x1B[0m".to_string(),
-            'b' => "\x1B[1;34mBlue\x1B[0m".to_string(),
-            'y' => "\x1B[1;33mYellow\x1B[0m".to_string(),
-            'g' => "\x1B[1;32mGreen\x1B[0m".to_string(),
-            _ => panic!()
+    // Players start from the corners of a board of the given size.
+    fn new(tag: char, size: usize) -> Option<Player> {
+        let pos = match tag {
+            'r' => (0, 0),
+            'b' => (0, size-1),
+            'y' => (size-1, 0),
+            'g' => (size-1, size-1),
+            _ => return None
+        };
+        Some(Player { tag, pos, objectives: vec![] })
+    }
+
+    fn targets(&self, rules: &RuleSet) -> &[TileContent] {
+        rules.targets(&self.objectives)
+    }
+}
+
+// Reasons why an action is rejected by the rules engine.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameError {
+    InvalidPlayer(char),
+    DuplicatePlayer(char),
+    PlayerCount(usize),
+    BoardSize(usize),
+    // The numbers of objectives are given neither once nor once per player.
+    ObjectiveLists(usize),
+    // A player would have no objective.
+    NoObjective,
+    // There are not enough objects on the board for all the objectives.
+    NotEnoughObjects(usize),
+    // The entry point does not exist on this board.
+    InvalidEntry(EntryPoint),
+    // The push would revert the previous one (the extra tile is there).
+    ReversePush(EntryPoint),
+    OutOfRange((usize, usize)),
+    Unreachable((usize, usize)),
+    GameOver,
+    // Pushing twice in the same turn.
+    AlreadyPushed,
+    // Moving before pushing.
+    NotPushed,
+    // A player acting when it is not its turn (see Game::apply_as).
+    NotYourTurn(char)
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::InvalidPlayer(tag) => write!(f, "{} is not a valid player!", tag),
+            GameError::DuplicatePlayer(_) => write!(f, "The same player cannot appear multiple times!"),
+            GameError::PlayerCount(n) => write!(f, "The number of players ({}) is not allowed by the rules!", n),
+            GameError::BoardSize(_) => write!(f, "The size of the board should be 5, 7 or 9!"),
+            GameError::ObjectiveLists(n) => write!(f, "The number of objectives should be given once or for each player, not {} times!", n),
+            GameError::NoObjective => write!(f, "Each player should have at least one objective!"),
+            GameError::NotEnoughObjects(n) => write!(f, "There are not enough objects on the board for {} objectives!", n),
+            GameError::InvalidEntry(_) => write!(f, "Invalid move!"),
+            GameError::ReversePush(_) => write!(f, "You cannot undo the previous move!"),
+            GameError::OutOfRange(_) => write!(f, "Invalid coordinates."),
+            GameError::Unreachable((row, col)) => write!(f, "You cannot reach row {}, column {}.", row+1, col+1),
+            GameError::GameOver => write!(f, "The game is over!"),
+            GameError::AlreadyPushed => write!(f, "The tile has already been pushed, now you have to move!"),
+            GameError::NotPushed => write!(f, "You have to push the tile before moving!"),
+            GameError::NotYourTurn(tag) => write!(f, "It is not the turn of player {}!", tag)
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+// What happened when the current player moved.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveOutcome {
+    Moved,
+    Collected { objective: TileContent, remaining: usize },
+    Won
+}
+
+// One half of a turn: every turn is a push followed by a move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Push(EntryPoint, Orientation),
+    Move((usize, usize))
+}
+
+impl Action {
+    // Actions are written as in the terminal: "1e" for a push from the '1'
+    // position oriented towards east, "35" for a move to row 3, column 5.
+    // The numbering of the entry points depends on the size of the board.
+    pub fn notation(self, size: usize) -> String {
+        match self {
+            Action::Push(entry, orientation) => format!("{}{}", entry.to_num(size), orientation.to_char()),
+            Action::Move((row, col)) => format!("{}{}", row+1, col+1)
         }
     }
 }
 
+// The part of the game state that is restored by undo.
+#[derive(Clone, Debug)]
+struct Snapshot {
+    players: Vec<Player>,
+    current_player: usize,
+    board: Board,
+    pushed: bool,
+    end: bool
+}
+
+// Games are deserialized through GameData, so that they are checked as when
+// they are loaded from a saved game. As when loading, the actions played
+// before cannot be undone: history() is kept, but undo() returns nothing.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "GameData"))]
 pub struct Game {
     players: Vec<Player>,
     current_player: usize,
     board: Board,
-    end: bool
+    seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rules: RuleSet,
+    pushed: bool,
+    end: bool,
+    // Actions played so far.
+    history: Vec<Action>,
+    // States preceding the most recent actions, which can be undone.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    snapshots: Vec<Snapshot>,
+    // Actions that have been undone and can be redone, most recent last.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undone: Vec<Action>
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct GameData {
+    players: Vec<Player>,
+    current_player: usize,
+    board: Board,
+    seed: u64,
+    #[serde(default)]
+    rules: RuleSet,
+    pushed: bool,
+    end: bool,
+    history: Vec<Action>
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<GameData> for Game {
+    type Error = String;
+
+    fn try_from(g: GameData) -> Result<Self, Self::Error> {
+        let size = g.board.size();
+        if size != g.rules.size {
+            return Err(format!("invalid board of size {} for the rules", size));
+        }
+        if g.players.is_empty() || g.players.len() < g.rules.min_players || g.players.len() > g.rules.max_players {
+            return Err(format!("invalid number of players {}", g.players.len()));
+        }
+        for (i, p) in g.players.iter().enumerate() {
+            if Player::new(p.tag, size).is_none() || g.players[..i].iter().any(|x| x.tag == p.tag) {
+                return Err(format!("invalid player {}", p.tag));
+            }
+            if p.pos.0 >= size || p.pos.1 >= size {
+                return Err(format!("invalid position of player {}", p.tag));
+            }
+        }
+        if g.current_player >= g.players.len() {
+            return Err(format!("invalid current player {}", g.current_player));
+        }
+
+        Ok(Game {
+            players: g.players,
+            current_player: g.current_player,
+            board: g.board,
+            seed: g.seed,
+            rules: g.rules,
+            pushed: g.pushed,
+            end: g.end,
+            history: g.history,
+            snapshots: vec![],
+            undone: vec![]
+        })
+    }
 }
 
 impl Game {
-    pub fn new() -> Game {
-        let mut g = Game {
-            players: Game::get_players(),
-            current_player: 0,
-            board: Board::new(),
-            end: false
-        };
+    // Start a new game with the given players ('r', 'b', 'y', 'g'),
+    // in the order in which they will play.
+    pub fn new(players: &[char]) -> Result<Game, GameError> {
+        Game::with_seed(players, thread_rng().gen())
+    }
 
-        g.deal_objectives();
-        g
+    // Start a new game whose board and objectives are entirely determined
+    // by the seed: the same seed always gives the same game.
+    pub fn with_seed(players: &[char], seed: u64) -> Result<Game, GameError> {
+        Game::with_size(players, 7, seed)
     }
 
-    pub fn play_turn(&mut self) {
-        println!("{}", self.draw_to_string());
-        self.get_push();
+    // Start a new game on a board of the given size (5, 7 or 9).
+    pub fn with_size(players: &[char], size: usize, seed: u64) -> Result<Game, GameError> {
+        Game::with_rules(players, &RuleSet { size, ..RuleSet::default() }, seed)
+    }
 
-        println!("{}", self.draw_to_string());
-        self.get_move();
+    // Start a new game with house rules.
+    pub fn with_rules(players: &[char], rules: &RuleSet, seed: u64) -> Result<Game, GameError> {
+        let size = rules.size;
+        if !Board::is_valid_size(size) {
+            return Err(GameError::BoardSize(size));
+        }
 
-        // Check the objectives.
-        let p = &mut self.players[self.current_player];
-        let colored_str = p.to_colored_str();
-        if p.objectives.last() == Some(&self.board.content_at(p.pos.0, p.pos.1)) {
-            let done = p.objectives.pop().unwrap();
-            let left = p.objectives.len();
-            if left > 2 {
-                println!("You reached the \x1B[1m{:?}\x1B[0m! You have now {} objectives to go!", done, p.objectives.len()-1);
-                self.board.remove_content(done);
-            }
-            else if left == 2 {
-                println!("You reached the \x1B[1m{:?}\x1B[0m! You have now only {} objective to go!", done, p.objectives.len()-1);
-                self.board.remove_content(done);
-            } else if left == 1 {
-                println!("You reached the \x1B[1m{:?}\x1B[0m! You just have to go back to the start tile (\"{}\") to win!", done, p.tag.to_ascii_uppercase());
-                self.board.remove_content(done);
-            } else {
-                // Victory!
-                println!("{}", self.draw_to_string());
-                println!("Player {} wins the game! Congratulations!", colored_str);
-                self.end = true;
+        let mut p: Vec<Player> = vec![];
+        for &tag in players {
+            match Player::new(tag, size) {
+                None => return Err(GameError::InvalidPlayer(tag)),
+                Some(_) if p.iter().any(|x| x.tag == tag) => return Err(GameError::DuplicatePlayer(tag)),
+                Some(player) => p.push(player)
             }
         }
 
-        // Go to the next player.
-        self.current_player = (self.current_player + 1) % self.players.len();
-    }
+        if p.is_empty() || p.len() < rules.min_players || p.len() > rules.max_players {
+            return Err(GameError::PlayerCount(p.len()));
+        }
 
-    fn get_players() -> Vec<Player> {
-        loop {
-            println!("Who is playing?");
-            println!("(for example: enter 'r,b' if the red and blue players are playing)");
-            let mut players_str = String::new();
-
-            std::io::stdin().read_line(&mut players_str)
-                .expect("Failed to read line");
-
-            // Read the list of players given in input.
-            let (mut r, mut b, mut y, mut g, mut err) = (0, 0, 0, 0, 0);
-            let players: Vec<_> = players_str.trim().split(',').filter_map(|x| {
-                match x.trim().to_ascii_lowercase().as_str() {
-                    "r" => { r += 1; Some(Player{ tag: 'r', pos: (0, 0), objectives: vec![] }) },
-                    "b" => { b += 1; Some(Player{ tag: 'b', pos: (0, 6), objectives: vec![] }) },
-                    "y" => { y += 1; Some(Player{ tag: 'y', pos: (6, 0), objectives: vec![] }) },
-                    "g" => { g += 1; Some(Player{ tag: 'g', pos: (6, 6), objectives: vec![] }) },
-                    _ => {
-                        err += 1;
-                        println!("{} is not a valid player!", x);
-                        None
-                    }
-                }
-            }).collect();
-
-            if err > 0 {
-                continue;
-            }
+        // The number of objectives is either the same for all the players,
+        // or given for each of them.
+        match rules.objectives.len() {
+            0 | 1 => {},
+            n if n == p.len() => {},
+            n => return Err(GameError::ObjectiveLists(n))
+        }
+        if rules.objectives.contains(&0) {
+            return Err(GameError::NoObjective);
+        }
 
-            if players.len() < 2 || players.len() > 4 {
-                println!("The number of players should be between 2 and 4!");
-                continue;
-            }
+        // The same generator is used for the board and the objectives.
+        let mut rng = StdRng::seed_from_u64(seed);
 
-            if r > 1 || b > 1 || y > 1 || g > 1 {
-                println!("The same player cannot appear multiple times!");
-                continue;
-            }
+        let board = Board::with_size(size, &mut rng);
 
-            println!();
-            return players;
+        let mut g = Game {
+            players: p,
+            current_player: 0,
+            board,
+            seed,
+            rules: rules.clone(),
+            pushed: false,
+            end: false,
+            history: vec![],
+            snapshots: vec![],
+            undone: vec![]
         };
-    }
 
-    fn deal_objectives(&mut self) {
-        // Shuffle the 24 objectives.
-        let mut obj = ["ba","be","bo","ca","cr","dr","fa","ge","gn","gh","go","he",
-                       "ke","ma","mo","ow","ra","ri","sa","sp","tr","tl","sk","sw"];
-        obj.shuffle(&mut thread_rng());
+        g.deal_objectives(&mut rng)?;
+        Ok(g)
+    }
 
-        let mut stack: Vec<_> = obj.iter().map(|o| TileContent::try_from_str(o).unwrap()).collect();
+    fn deal_objectives<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), GameError> {
+        // Shuffle the objects on the board (24 on a 7x7 board).
+        let mut stack = self.board.objects();
+        stack.shuffle(rng);
+
+        // Unless the numbers are given, distribute the objectives evenly.
+        let num_players = self.players.len();
+        let mut counts = match self.rules.objectives.as_slice() {
+            [] => vec![stack.len() / num_players; num_players],
+            [n] => vec![*n; num_players],
+            counts => counts.to_vec()
+        };
+        let total = counts.iter().sum();
+        if total > stack.len() {
+            return Err(GameError::NotEnoughObjects(total));
+        }
 
         // Add the final objective: going back to the home tile.
-        for p in &mut self.players {
-            p.objectives.push(TileContent::try_from_str(&p.tag.to_string()).unwrap());
+        if self.rules.return_home {
+            for p in &mut self.players {
+                p.objectives.push(TileContent::try_from_str(&p.tag.to_string()).unwrap());
+            }
         }
 
-        // Distribute the 24 objectives.
+        // Deal them one at a time to the players who still need some: what
+        // is left over stays on the board only as an obstacle.
         let mut idx = 0;
-        while let Some(obj) = stack.pop() {
-            self.players[idx].objectives.push(obj);
+        while counts.iter().any(|n| *n > 0) {
+            if counts[idx] > 0 {
+                self.players[idx].objectives.push(stack.pop().unwrap());
+                counts[idx] -= 1;
+            }
             idx = (idx + 1) % self.players.len();
         }
+        Ok(())
     }
 
-    fn get_push(&mut self) {
-        let p = &self.players[self.current_player];
-        let (row, col) = p.pos;
-        let obj = p.objectives.last().unwrap();
-        let obj_str = format!("{:?}", obj);
-        let [obj_c1, obj_c2] = obj.to_chars();
-
-        loop {
-            println!("Player {}, it's your turn. Shift the labyrinth!", p.to_colored_str());
-            println!("Your objective is: \x1B[1m{} (\"{}{}\")\x1B[0m.", obj_str, obj_c1, obj_c2);
-            println!();
-            println!("Where do you put the tile?");
-            println!("(for example: enter '1e' to push the tile from the '1' position, oriented towards east)");
-            println!("(type \"hint\" for a hint)");
-            //println!("Reachable objects in 1 move: {:?}", self.board.objects_reachable_in_1_move_from(row, col));
-
-            let mut s = String::new();
-
-            std::io::stdin().read_line(&mut s)
-                .expect("Failed to read line");
-
-            // Display hint.
-            if s.trim().to_ascii_lowercase() == "hint" {
-                self.hint(row, col, *obj);
-                continue;
-            }
-
-            // Check if the push given in input is valid.
-            let entry: String = s.chars().filter(|c| c.is_digit(10)).collect();
-            let entry = match entry.as_str() {
-                "1" => EntryPoint::NorthLeft,
-                "2" => EntryPoint::NorthCenter,
-                "3" => EntryPoint::NorthRight,
-                "4" => EntryPoint::EastUp,
-                "5" => EntryPoint::EastCenter,
-                "6" => EntryPoint::EastDown,
-                "7" => EntryPoint::SouthRight,
-                "8" => EntryPoint::SouthCenter,
-                "9" => EntryPoint::SouthLeft,
-                "10" => EntryPoint::WestDown,
-                "11" => EntryPoint::WestCenter,
-                "12" => EntryPoint::WestUp,
-                _ => {
-                    println!("Invalid move!");
-                    continue;
-                }
-            };
-
-            if Some(entry) == self.board.extra_tile_position() {
-                println!("You cannot undo the previous move!");
-                continue;
-            }
+    // First half of a turn: the current player shifts the labyrinth.
+    pub fn apply_push(&mut self, entry: EntryPoint, orientation: Orientation) -> Result<(), GameError> {
+        if self.end {
+            return Err(GameError::GameOver);
+        }
 
-            let orientation: String = s.trim().chars().filter(|c| !c.is_digit(10)).collect();
-            let orientation = match orientation.as_str() {
-                "n" => Orientation::North,
-                "e" => Orientation::East,
-                "s" => Orientation::South,
-                "w" => Orientation::West,
-                _ => {
-                    println!("Invalid move!");
-                    continue;
-                }
-            };
+        if self.pushed {
+            return Err(GameError::AlreadyPushed);
+        }
 
-            // The push is valid, apply it.
-            let mut player_positions: Vec<_> = self.players.iter_mut().map(|p| &mut p.pos).collect();
-            self.board.push_tile(entry, orientation, &mut player_positions);
+        if !self.board.is_valid_entry(entry) {
+            return Err(GameError::InvalidEntry(entry));
+        }
 
-            return;
+        if !self.rules.reverse_push && self.board.is_reverse_push(entry) {
+            return Err(GameError::ReversePush(entry));
         }
+
+        self.record(Action::Push(entry, orientation));
+
+        let mut player_positions: Vec<_> = self.players.iter_mut().map(|p| &mut p.pos).collect();
+        self.board.push_tile(entry, orientation, &mut player_positions, &self.rules);
+        self.pushed = true;
+        Ok(())
     }
 
-    fn get_move(&mut self) {
+    // Second half of a turn: the current player moves to a reachable
+    // position, possibly collecting an objective.
+    pub fn apply_move(&mut self, next_pos: (usize, usize)) -> Result<MoveOutcome, GameError> {
+        if self.end {
+            return Err(GameError::GameOver);
+        }
+
+        if !self.pushed {
+            return Err(GameError::NotPushed);
+        }
+
+        if next_pos.0 >= self.board.size() || next_pos.1 >= self.board.size() {
+            return Err(GameError::OutOfRange(next_pos));
+        }
+
         let (row, col) = self.players[self.current_player].pos;
-        let reachable = self.board.reachable_from(row, col);
-
-        loop {
-            println!("Player {}, you are in row {}, column {}. Where do you move?", self.players[self.current_player].to_colored_str(), row+1, col+1);
-            println!("(for example: enter '35' to go to row 3, column 5. Or enter 'Sk' to go the Skull)");
-            //println!("Reachable objects: {:?}", self.board.objects_reachable_from(row, col));
-
-            let mut s = String::new();
-
-            std::io::stdin().read_line(&mut s)
-                .expect("Failed to read line");
-
-            // Check if the move given in input is valid.
-            let mut next_pos = if let Some(obj) = TileContent::try_from_str(&s) {
-                self.board.content_position(obj)
-            } else {
-                None
-            };
-
-            if next_pos == None {
-                let d: Vec<_> = s.chars().filter(|c| c.is_digit(10)).collect();
-                if d.len() < 2 {
-                    println!("Invalid coordinates.");
-                    continue;
-                }
-                next_pos = Some(((d[0].to_digit(10).unwrap()-1) as usize, (d[1].to_digit(10).unwrap()-1) as usize));
-            }
+        if !self.board.reachable_from(row, col).contains(&next_pos) {
+            return Err(GameError::Unreachable(next_pos));
+        }
 
-            let next_pos = next_pos.unwrap();
-            if next_pos.0 >= 7 || next_pos.1 >= 7 {
-                println!("Invalid coordinates.");
-                continue;
-            }
+        // The move is valid, update the player position.
+        self.record(Action::Move(next_pos));
 
-            if !reachable.contains(&next_pos) {
-                println!("You cannot reach row {}, column {}.", next_pos.0+1, next_pos.1+1);
-                continue;
-            }
+        let p = &mut self.players[self.current_player];
+        p.pos = next_pos;
+        self.pushed = false;
 
-            // The move is valid, update the player position.
-            self.players[self.current_player].pos = next_pos;
-            return;
-        }
-    }
-
-    fn hint(&self, row: usize, col: usize, obj: TileContent) {
-        let reach = objects_reachable_in_1_move_from(&self.board, row, col);
-
-        println!();
-        match (reach.get(&obj), self.players.len()) {
-            (None, 2) => {
-                // With 2 players, we look one move ahead.
-                println!("The {:?} is not reachable in one move. Analyzing possible scenarios...", obj);
-
-                let scenarios = object_reachable_scenarios(&self.board, row, col, obj);
-                let ((best_push, best_move), best_n) = scenarios.into_iter().max_by(|&(_, n1), &(_, n2)| usize::cmp(&n1, &n2)).unwrap();
-
-                match best_n {
-                    0 => {
-                        println!("The {:?} is not reachable in two moves either.", obj);
-                    },
-                    1 ..= 24 => {
-                        println!("You should consider pushing \"{}{}\" and then moving to \"{} {}\".", Game::entry_num(best_push.0), Game::orientation_char(best_push.1), best_move.0+1, best_move.1+1);
-                        println!("Depending on what your opponent does, you have some chance to get to the {:?} on the next move.", obj);
-                    },
-                    25 ..= 47 => {
-                        println!("You should consider pushing \"{}{}\" and then moving to \"{} {}\".", Game::entry_num(best_push.0), Game::orientation_char(best_push.1), best_move.0+1, best_move.1+1);
-                        println!("Depending on what your opponent does, you have a good chance to get to the {:?} on the next move.", obj);
-                    },
-                    _ => {
-                        println!("You should consider pushing \"{}{}\" and then moving to \"{} {}\".", Game::entry_num(best_push.0), Game::orientation_char(best_push.1), best_move.0+1, best_move.1+1);
-                        println!("Irrespective of what your opponent does, you will be able to get to the {:?} on the next move.", obj);
-                    }
-                }
-            },
-            (None, _) => {
-                // We don't do further analysis if there are more than 2 players.
-                println!("The {:?} is not reachable in one move.", obj);
-            },
-            (Some(v), _) => {
-                let v_num: Vec<_> = v.into_iter().map(|e| Game::entry_num(*e)).collect();
-
-                if v_num.len() == 1 {
-                    println!("The {:?} is reachable in one move: push from the {} position.", obj, v_num[0]);
-                }
-                else {
-                    println!("The {:?} is reachable in one move, push from one of the positions: {:?}.", obj, v_num);
-                }
+        // Check the objectives.
+        let mut outcome = MoveOutcome::Moved;
+        let content = self.board.content_at(p.pos.0, p.pos.1);
+        if p.targets(&self.rules).contains(&content) {
+            let i = p.objectives.iter().rposition(|o| *o == content).unwrap();
+            let done = p.objectives.remove(i);
+            if p.objectives.is_empty() {
+                // Victory! The winner stays the current player.
+                self.end = true;
+                return Ok(MoveOutcome::Won);
             }
+            self.board.remove_content(done);
+            outcome = MoveOutcome::Collected { objective: done, remaining: p.objectives.len() };
         }
-        println!();
+
+        // Go to the next player.
+        self.current_player = (self.current_player + 1) % self.players.len();
+        Ok(outcome)
+    }
+
+    // Apply either half of a turn. The outcome is only given for moves.
+    // The methods above act for the current player, whoever calls them:
+    // checking who is playing is left to the caller, or to apply_as.
+    pub fn apply(&mut self, action: Action) -> Result<Option<MoveOutcome>, GameError> {
+        match action {
+            Action::Push(entry, orientation) => self.apply_push(entry, orientation).map(|_| None),
+            Action::Move(pos) => self.apply_move(pos).map(Some)
+        }
+    }
+
+    // The same, on behalf of a given player, e.g. for actions coming from
+    // the network: they are rejected if it is not the turn of the player.
+    pub fn apply_as(&mut self, tag: char, action: Action) -> Result<Option<MoveOutcome>, GameError> {
+        if !self.players.iter().any(|p| p.tag == tag) {
+            return Err(GameError::InvalidPlayer(tag));
+        }
+        if self.end {
+            return Err(GameError::GameOver);
+        }
+        if self.current_player() != tag {
+            return Err(GameError::NotYourTurn(tag));
+        }
+        self.apply(action)
+    }
+
+    fn record(&mut self, action: Action) {
+        let snapshot = Snapshot {
+            players: self.players.clone(),
+            current_player: self.current_player,
+            board: self.board,
+            pushed: self.pushed,
+            end: self.end
+        };
+        self.history.push(action);
+        self.snapshots.push(snapshot);
+        self.undone.clear();
+    }
+
+    // Revert the last action (push or move), returning it.
+    pub fn undo(&mut self) -> Option<Action> {
+        let snapshot = self.snapshots.pop()?;
+        let action = self.history.pop().unwrap();
+        self.players = snapshot.players;
+        self.current_player = snapshot.current_player;
+        self.board = snapshot.board;
+        self.pushed = snapshot.pushed;
+        self.end = snapshot.end;
+        self.undone.push(action);
+        Some(action)
+    }
+
+    // Play again the last undone action, returning it.
+    pub fn redo(&mut self) -> Option<Action> {
+        let action = self.undone.pop()?;
+
+        // Replaying the action must not forget the other undone actions.
+        let undone = std::mem::take(&mut self.undone);
+        self.apply(action).expect("an undone action is valid when redone");
+        self.undone = undone;
+        Some(action)
+    }
+
+    // All the actions played so far, in order.
+    pub fn history(&self) -> &[Action] {
+        &self.history
     }
 
-    fn entry_num(entry: EntryPoint) -> u8 {
-        match entry {
-            EntryPoint::NorthLeft => 1,
-            EntryPoint::NorthCenter => 2,
-            EntryPoint::NorthRight => 3,
-            EntryPoint::EastUp => 4,
-            EntryPoint::EastCenter => 5,
-            EntryPoint::EastDown => 6,
-            EntryPoint::SouthRight => 7,
-            EntryPoint::SouthCenter => 8,
-            EntryPoint::SouthLeft => 9,
-            EntryPoint::WestDown => 10,
-            EntryPoint::WestCenter => 11,
-            EntryPoint::WestUp => 12
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    // The players, in the order in which they play.
+    pub fn players(&self) -> Vec<char> {
+        self.players.iter().map(|p| p.tag).collect()
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn current_player(&self) -> char {
+        self.players[self.current_player].tag
+    }
+
+    pub fn current_position(&self) -> (usize, usize) {
+        self.players[self.current_player].pos
+    }
+
+    // The positions of all the players, in the order in which they play.
+    pub fn positions(&self) -> Vec<(usize, usize)> {
+        self.players.iter().map(|p| p.pos).collect()
+    }
+
+    // The number of objectives left to each player (including going back
+    // home, if it is in the rules), in the order in which they play.
+    pub fn num_objectives(&self) -> Vec<usize> {
+        self.players.iter().map(|p| p.objectives.len()).collect()
+    }
+
+    // The objectives that the current player can collect now: only one,
+    // unless they can be collected in any order.
+    pub fn current_objectives(&self) -> Vec<TileContent> {
+        self.players[self.current_player].targets(&self.rules).to_vec()
+    }
+
+    // A hint for the turn of the current player, before it pushes.
+    pub fn hint(&self) -> Option<Hint> {
+        if self.end || self.pushed {
+            return None;
         }
+        let (row, col) = self.current_position();
+        Some(analyzer::hint(&self.board, &self.rules, row, col, &self.current_objectives(), self.players.len()-1))
     }
 
-    fn orientation_char(orientation: Orientation) -> char {
-        match orientation {
-            Orientation::North => 'n',
-            Orientation::East => 'e',
-            Orientation::South => 's',
-            Orientation::West => 'w',
+    // The same, reusing the results of the previous hints.
+    pub fn hint_with_cache(&self, cache: &mut Cache) -> Option<Hint> {
+        if self.end || self.pushed {
+            return None;
         }
+        let (row, col) = self.current_position();
+        Some(cache.hint(&self.board, &self.rules, row, col, &self.current_objectives(), self.players.len()-1))
+    }
+
+    // Whether the current player has pushed and has yet to move.
+    pub fn has_pushed(&self) -> bool {
+        self.pushed
     }
 
     pub fn is_over(&self) -> bool {
@@ -342,7 +528,13 @@ impl Game {
     }
 
     pub fn draw_to_string(&self) -> String {
-        let mut b = self.board.to_ascii_29x56();
+        self.draw_to_string_with_path(&[])
+    }
+
+    // Draw the board, with a path (e.g. the last move) on it.
+    pub fn draw_to_string_with_path(&self, path: &[(usize, usize)]) -> String {
+        let mut b = self.board.to_ascii();
+        self.board.put_ascii_path(&mut b, path);
 
         // Draw players.
         for p in &self.players {
@@ -354,10 +546,8 @@ impl Game {
         b[3*(1+p.pos.0)+1][6*(1+p.pos.1)+5] = p.tag;
 
         let mut s = String::new();
-        for r in 0..29 {
-            for c in 0..56 {
-                s.push(b[r][c]);
-            }
+        for row in b.iter() {
+            s.extend(row.iter());
             s.push('\n');
         }
 
@@ -371,8 +561,74 @@ impl Game {
     }
 }
 
-impl Default for Game {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::{GreedyStrategy, Strategy};
+
+    fn objective_stacks(g: &Game) -> Vec<Vec<TileContent>> {
+        g.players.iter().map(|p| p.objectives.clone()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_game() {
+        for &seed in &[0, 1, 1234] {
+            let a = Game::with_seed(&['r', 'b', 'y'], seed).unwrap();
+            let b = Game::with_seed(&['r', 'b', 'y'], seed).unwrap();
+            assert_eq!(a.board().to_ascii(), b.board().to_ascii());
+            assert_eq!(a.board().zobrist_hash(), b.board().zobrist_hash());
+            assert_eq!(objective_stacks(&a), objective_stacks(&b));
+        }
+
+        let a = Game::with_seed(&['r', 'b'], 1).unwrap();
+        let b = Game::with_seed(&['r', 'b'], 2).unwrap();
+        assert_ne!(objective_stacks(&a), objective_stacks(&b));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        // Play until an objective is collected, saving the game after each
+        // action.
+        let mut game = Game::with_seed(&['r', 'b'], 1).unwrap();
+        let mut bot = GreedyStrategy::with_seed(1);
+        let mut states = vec![game.save_to_string()];
+        let mut collected = false;
+        while !collected {
+            let (entry, orientation) = bot.choose_push(&game.current_view());
+            game.apply_push(entry, orientation).unwrap();
+            states.push(game.save_to_string());
+
+            let (row, col) = game.current_position();
+            let reachable = game.board().reachable_from(row, col);
+            let pos = bot.choose_move(&game.current_view(), &reachable);
+            collected = matches!(game.apply_move(pos).unwrap(), MoveOutcome::Collected { .. });
+            states.push(game.save_to_string());
+        }
+        let actions = game.history().to_vec();
+
+        // Back to the start, one action at a time.
+        for (i, action) in actions.iter().enumerate().rev() {
+            assert_eq!(game.undo(), Some(*action));
+            assert_eq!(game.save_to_string(), states[i]);
+        }
+        assert_eq!(game.undo(), None);
+
+        // And forward again to the end.
+        for (i, action) in actions.iter().enumerate() {
+            assert_eq!(game.redo(), Some(*action));
+            assert_eq!(game.save_to_string(), states[i+1]);
+        }
+        assert_eq!(game.redo(), None);
+
+        // Another push after undoing the last turn forgets the undone moves.
+        game.undo();
+        game.undo();
+        let last_push = actions[actions.len()-2];
+        let (entry, orientation) = game.board().legal_pushes(game.rules()).into_iter()
+            .find(|(e, o)| Action::Push(*e, *o) != last_push).unwrap();
+        game.apply_push(entry, orientation).unwrap();
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.history().len(), actions.len() - 1);
+        assert_ne!(game.save_to_string(), states[actions.len() - 1]);
     }
 }
@@ -0,0 +1,343 @@
+use super::{Action, Game, Player};
+use crate::record::{parse_position, parse_push};
+use crate::board::{Board, EntryPoint, Orientation, Tile, TileContent, TileKind};
+use crate::rules::{bool_from_str, yes_no, RuleSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Saved games are plain text files, one item per line:
+//
+//   size 7
+//   variant Standard
+//   reverse-push no
+//   wrap-around yes
+//   return-home yes
+//   players 2 4
+//   seed 1234
+//   current 0
+//   pushed no
+//   over no
+//   extra J Ba 9
+//   row Ce-R JnSk Se- ...
+//   ...
+//   player r 1 1 Ge Sw R
+//   player b 1 7 Ow Ma B
+//   history 1e 11 2n 17
+//
+// The rules come first, as in a configuration file (see RuleSet), with the
+// size of the board at the very beginning. The rules that are not given
+// keep their default value. Then, each "row" line
+// lists the tiles of a row of the board as
+// kind, orientation and content (or "-" for nothing). The extra tile is
+// given by kind, content and entry point (or "-" before the first push).
+// Players are listed in the order in which they play, with their position
+// and their objectives, starting from the current one. The history lists
+// the actions played so far, so that the record of the game is kept.
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // A line that could not be understood.
+    Parse(usize, String),
+    // An item that is missing from the file, or inconsistent with the rest.
+    Invalid(&'static str)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "Cannot read the saved game: {}", e),
+            LoadError::Parse(line, msg) => write!(f, "Invalid saved game, line {}: {}", line, msg),
+            LoadError::Invalid(item) => write!(f, "Invalid saved game: missing or invalid {}", item)
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+fn content_to_string(content: TileContent) -> String {
+    match content {
+        TileContent::None => "-".to_string(),
+        _ => content.to_chars().iter().filter(|c| **c != ' ').collect()
+    }
+}
+
+fn content_from_str(s: &str) -> Option<TileContent> {
+    match s {
+        "-" => Some(TileContent::None),
+        _ => TileContent::try_from_str(s)
+    }
+}
+
+fn tile_from_str(s: &str) -> Option<(Tile, Orientation)> {
+    let mut chars = s.chars();
+    let kind = TileKind::try_from_char(chars.next()?)?;
+    let orientation = Orientation::try_from_char(chars.next()?)?;
+    let content = content_from_str(chars.as_str())?;
+    Some((Tile { kind, content }, orientation))
+}
+
+fn position_from_str(row: &str, col: &str, size: usize) -> Option<(usize, usize)> {
+    let row: usize = row.parse().ok()?;
+    let col: usize = col.parse().ok()?;
+    if !(1..=size).contains(&row) || !(1..=size).contains(&col) {
+        return None;
+    }
+    Some((row-1, col-1))
+}
+
+impl Game {
+    // Write the full state of the game as text. Past states are not saved,
+    // so the actions played before saving cannot be undone after loading.
+    pub fn save_to_string(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("# Labyrinth saved game\n");
+        s.push_str(&self.rules.to_string());
+        s.push_str(&format!("seed {}\n", self.seed));
+        s.push_str(&format!("current {}\n", self.current_player));
+        s.push_str(&format!("pushed {}\n", yes_no(self.pushed)));
+        s.push_str(&format!("over {}\n", yes_no(self.end)));
+
+        let extra = self.board.extra_tile();
+        let extra_pos = match self.board.extra_tile_position() {
+            Some(entry) => entry.to_num(self.board.size()).to_string(),
+            None => "-".to_string()
+        };
+        s.push_str(&format!("extra {} {} {}\n", extra.kind.to_char(), content_to_string(extra.content), extra_pos));
+
+        for row in 0..self.board.size() {
+            s.push_str("row");
+            for col in 0..self.board.size() {
+                let (tile, orientation) = self.board.tile_at(row, col);
+                s.push_str(&format!(" {}{}{}", tile.kind.to_char(), orientation.to_char(), content_to_string(tile.content)));
+            }
+            s.push('\n');
+        }
+
+        for p in &self.players {
+            s.push_str(&format!("player {} {} {}", p.tag, p.pos.0+1, p.pos.1+1));
+            for obj in p.objectives.iter().rev() {
+                s.push(' ');
+                s.push_str(&content_to_string(*obj));
+            }
+            s.push('\n');
+        }
+
+        if !self.history.is_empty() {
+            s.push_str("history");
+            for action in &self.history {
+                s.push_str(&format!(" {}", action.notation(self.board.size())));
+            }
+            s.push('\n');
+        }
+
+        s
+    }
+
+    pub fn load_from_str(s: &str) -> Result<Game, LoadError> {
+        let (mut seed, mut current, mut pushed, mut end, mut extra) = (None, None, None, None, None);
+        let mut size = None;
+        let mut rules = RuleSet::default();
+        let mut tiles = vec![];
+        let mut players: Vec<Player> = vec![];
+        let mut history = vec![];
+
+        for (i, line) in s.lines().enumerate() {
+            let err = |msg: &str| LoadError::Parse(i+1, msg.to_string());
+
+            let words: Vec<_> = line.split_whitespace().collect();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+
+            // Everything else depends on the size of the board.
+            if words[0] == "size" {
+                match (size, &words[1..]) {
+                    (None, [x]) => size = Some(x.parse().ok().filter(|n| Board::is_valid_size(*n)).ok_or_else(|| err("invalid size"))?),
+                    _ => return Err(err("the size should be given once, at the beginning"))
+                }
+                continue;
+            }
+            let n = *size.get_or_insert(7);
+
+            match (words[0], &words[1..]) {
+                ("seed", [x]) => {
+                    seed = Some(x.parse().map_err(|_| err("invalid seed"))?);
+                },
+                ("current", [x]) => {
+                    current = Some(x.parse().map_err(|_| err("invalid current player"))?);
+                },
+                ("pushed", [x]) => {
+                    pushed = Some(bool_from_str(x).ok_or_else(|| err("expected \"yes\" or \"no\""))?);
+                },
+                ("over", [x]) => {
+                    end = Some(bool_from_str(x).ok_or_else(|| err("expected \"yes\" or \"no\""))?);
+                },
+                ("extra", [kind, content, pos]) => {
+                    let mut kind_chars = kind.chars();
+                    let kind = match (kind_chars.next().and_then(TileKind::try_from_char), kind_chars.next()) {
+                        (Some(kind), None) => kind,
+                        _ => return Err(err("invalid tile kind"))
+                    };
+                    let content = content_from_str(content).ok_or_else(|| err("invalid tile content"))?;
+                    let pos = match *pos {
+                        "-" => None,
+                        _ => Some(pos.parse().ok().and_then(|num| EntryPoint::try_from_num(num, n)).ok_or_else(|| err("invalid entry point"))?)
+                    };
+                    extra = Some((Tile { kind, content }, pos));
+                },
+                ("row", row) => {
+                    if row.len() != n || tiles.len() == n*n {
+                        return Err(err(&format!("expected {} rows of {} tiles", n, n)));
+                    }
+                    for t in row {
+                        tiles.push(tile_from_str(t).ok_or_else(|| err(&format!("invalid tile \"{}\"", t)))?);
+                    }
+                },
+                ("player", [tag, row, col, objectives @ ..]) => {
+                    let mut tag_chars = tag.chars();
+                    let mut p = match (tag_chars.next().and_then(|tag| Player::new(tag, n)), tag_chars.next()) {
+                        (Some(p), None) => p,
+                        _ => return Err(err("invalid player"))
+                    };
+                    if players.iter().any(|x| x.tag == p.tag) {
+                        return Err(err("duplicate player"));
+                    }
+                    p.pos = position_from_str(row, col, n).ok_or_else(|| err("invalid position"))?;
+                    for obj in objectives.iter().rev() {
+                        p.objectives.push(content_from_str(obj).ok_or_else(|| err(&format!("invalid objective \"{}\"", obj)))?);
+                    }
+                    players.push(p);
+                },
+                ("history", actions) => {
+                    // Pushes and moves alternate, starting from a push.
+                    for (j, a) in actions.iter().enumerate() {
+                        let action = if j % 2 == 0 {
+                            parse_push(a, n).map(|(entry, orientation)| Action::Push(entry, orientation))
+                        } else {
+                            parse_position(a).filter(|p| p.0 < n && p.1 < n).map(Action::Move)
+                        };
+                        history.push(action.ok_or_else(|| err(&format!("invalid action \"{}\"", a)))?);
+                    }
+                },
+                (key, values) => match rules.set(key, values) {
+                    Some(res) => res.map_err(|msg| err(&msg))?,
+                    None => return Err(err("unexpected line"))
+                }
+            }
+        }
+
+        let size = size.unwrap_or(7);
+        if tiles.len() != size*size {
+            return Err(LoadError::Invalid("board rows"));
+        }
+        if players.len() < rules.min_players || players.len() > rules.max_players {
+            return Err(LoadError::Invalid("players"));
+        }
+        let current_player = current.ok_or(LoadError::Invalid("current player"))?;
+        if current_player >= players.len() {
+            return Err(LoadError::Invalid("current player"));
+        }
+        let (extra_tile, extra_pos) = extra.ok_or(LoadError::Invalid("extra tile"))?;
+        if rules.objectives.len() > 1 && rules.objectives.len() != players.len() {
+            return Err(LoadError::Invalid("objectives"));
+        }
+        rules.size = size;
+        let board = Board::from_tiles(size, tiles, extra_tile, extra_pos);
+
+        Ok(Game {
+            players,
+            current_player,
+            board,
+            seed: seed.ok_or(LoadError::Invalid("seed"))?,
+            rules,
+            pushed: pushed.ok_or(LoadError::Invalid("pushed"))?,
+            end: end.ok_or(LoadError::Invalid("over"))?,
+            history,
+            snapshots: vec![],
+            undone: vec![]
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.save_to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Game, LoadError> {
+        Game::load_from_str(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Variant;
+    use crate::strategy::{GreedyStrategy, Strategy};
+
+    // A game with some turns played, stopped after a push.
+    fn played_game(players: &[char], rules: &RuleSet, seed: u64) -> Game {
+        let mut game = Game::with_rules(players, rules, seed).unwrap();
+        let mut bot = GreedyStrategy::with_seed(seed);
+        for _ in 0..6 {
+            let (entry, orientation) = bot.choose_push(&game.current_view());
+            game.apply_push(entry, orientation).unwrap();
+            let (row, col) = game.current_position();
+            let reachable = game.board().reachable_from(row, col);
+            let pos = bot.choose_move(&game.current_view(), &reachable);
+            game.apply_move(pos).unwrap();
+        }
+        let (entry, orientation) = bot.choose_push(&game.current_view());
+        game.apply_push(entry, orientation).unwrap();
+        game
+    }
+
+    #[test]
+    fn round_trip() {
+        let games = [
+            played_game(&['r', 'b'], &RuleSet { size: 5, ..RuleSet::default() }, 1),
+            played_game(&['r', 'b', 'y'], &RuleSet::default(), 2),
+            played_game(&['g', 'r'], &RuleSet { size: 9, variant: Variant::AnyOrder, objectives: vec![4, 7], ..RuleSet::default() }, 3),
+            played_game(&['b', 'y'], &RuleSet { objectives: vec![5], reverse_push: true, wrap_around: false, return_home: false, ..RuleSet::default() }, 4)
+        ];
+        for game in &games {
+            let saved = game.save_to_string();
+            let loaded = Game::load_from_str(&saved).unwrap();
+            assert_eq!(loaded.save_to_string(), saved);
+            assert_eq!(loaded.rules(), game.rules());
+            assert_eq!(loaded.current_player(), game.current_player());
+            assert_eq!(loaded.positions(), game.positions());
+            assert_eq!(loaded.num_objectives(), game.num_objectives());
+            assert_eq!(loaded.history(), game.history());
+            assert_eq!(loaded.board().zobrist_hash(), game.board().zobrist_hash());
+        }
+    }
+
+    #[test]
+    fn invalid_saves() {
+        let saved = played_game(&['r', 'b'], &RuleSet::default(), 1).save_to_string();
+        let line = |prefix: &str| saved.lines().find(|l| l.starts_with(prefix)).unwrap();
+
+        // A row with a missing tile.
+        let row = line("row ");
+        let short_row = saved.replacen(row, row.rsplit_once(' ').unwrap().0, 1);
+        assert!(matches!(Game::load_from_str(&short_row), Err(LoadError::Parse(_, _))));
+
+        // The same player twice.
+        let player = line("player r ");
+        let duplicate = saved.replacen(player, &format!("{}\n{}", player, player), 1);
+        assert!(matches!(Game::load_from_str(&duplicate), Err(LoadError::Parse(_, msg)) if msg == "duplicate player"));
+
+        // A current player that does not exist.
+        let current = saved.replacen(line("current "), "current 2", 1);
+        assert!(matches!(Game::load_from_str(&current), Err(LoadError::Invalid("current player"))));
+    }
+}
@@ -0,0 +1,71 @@
+use super::Game;
+use crate::board::{Board, TileContent};
+use crate::rules::RuleSet;
+
+// What a player can see of the game: everything except the objectives of
+// the other players, of which only the number is known. Bots and remote
+// players should be given their view rather than the game itself.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerView {
+    // The player seeing the game.
+    pub tag: char,
+    pub board: Board,
+    // The players, their positions and their number of objectives left
+    // (including going back home, if it is in the rules), in the order in
+    // which they play.
+    pub players: Vec<char>,
+    pub positions: Vec<(usize, usize)>,
+    pub num_objectives: Vec<usize>,
+    pub rules: RuleSet,
+    // The objectives that the player can collect now (the whole hand in the
+    // any-order variant), or nothing if the game is won.
+    pub objectives: Vec<TileContent>,
+    pub current_player: char,
+    pub pushed: bool,
+    pub over: bool
+}
+
+impl PlayerView {
+    fn index(&self) -> usize {
+        self.players.iter().position(|p| *p == self.tag).unwrap()
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        self.positions[self.index()]
+    }
+
+    pub fn num_own_objectives(&self) -> usize {
+        self.num_objectives[self.index()]
+    }
+
+    // Whether it is the turn of the player.
+    pub fn is_turn(&self) -> bool {
+        !self.over && self.current_player == self.tag
+    }
+}
+
+impl Game {
+    // The view of the game of the given player, if it is playing.
+    pub fn view(&self, tag: char) -> Option<PlayerView> {
+        let player = self.players.iter().find(|p| p.tag == tag)?;
+
+        Some(PlayerView {
+            tag,
+            board: self.board,
+            players: self.players(),
+            positions: self.positions(),
+            num_objectives: self.num_objectives(),
+            rules: self.rules.clone(),
+            objectives: player.targets(&self.rules).to_vec(),
+            current_player: self.current_player(),
+            pushed: self.pushed,
+            over: self.end
+        })
+    }
+
+    // The view of the player whose turn it is.
+    pub fn current_view(&self) -> PlayerView {
+        self.view(self.current_player()).unwrap()
+    }
+}
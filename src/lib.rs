@@ -1,5 +1,10 @@
 pub mod analyzer;
 pub mod board;
 pub mod game;
+pub mod mcts;
+pub mod record;
+pub mod rules;
+pub mod strategy;
 
-pub use game::Game;
+pub use game::{Action, Game, GameError, LoadError, PlayerView};
+pub use rules::{RuleSet, Variant};
@@ -0,0 +1,329 @@
+use crate::board::{Board, EntryPoint, Orientation, TileContent};
+use crate::game::{Action, PlayerView};
+use crate::rules::RuleSet;
+use crate::strategy::{distance_to, Strategy};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+// How long the bot thinks before each turn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration)
+}
+
+// Exploration constant of the selection (UCT).
+const EXPLORATION: f64 = 0.7;
+
+// Only the moves to the cells closest to an objective are searched, or only
+// the ones collecting an objective if there are any.
+const MAX_MOVES: usize = 4;
+
+// Playouts go on until the end of the game, which takes a few thousand turns
+// for random players on the largest boards. As a safety net, they are stopped
+// after this number of turns, and the position is then evaluated by the
+// progress of each player.
+const MAX_PLAYOUT_TURNS: usize = 10000;
+
+// The state of a simulated game. Unlike in Game, the objectives of the
+// opponents are not known to the bot: they are sampled before simulating.
+#[derive(Clone)]
+struct State {
+    board: Board,
+    positions: Vec<(usize, usize)>,
+    objectives: Vec<Vec<TileContent>>,
+    rules: RuleSet,
+    current: usize,
+    pushed: bool,
+    winner: Option<usize>
+}
+
+impl State {
+    // A possible state of the game, from the point of view of the current
+    // player: the objectives that it cannot see are drawn at random among
+    // the objects still on the board.
+    fn sample<R: Rng>(view: &PlayerView, rng: &mut R) -> State {
+        let players = &view.players;
+        let current = players.iter().position(|p| *p == view.current_player).unwrap();
+        let home = |tag: char| TileContent::try_from_str(&tag.to_string()).unwrap();
+        let known: Vec<_> = view.objectives.iter().copied().filter(|o| *o != home(view.current_player)).collect();
+
+        let mut pool: Vec<_> = view.board.objects().into_iter().filter(|o| !known.contains(o)).collect();
+        pool.shuffle(rng);
+
+        let objectives = players.iter().zip(&view.num_objectives).enumerate().map(|(i, (tag, &n))| {
+            // The last objective is going back home, if it is in the rules.
+            let mut stack = vec![];
+            if view.rules.return_home {
+                stack.push(home(*tag));
+            }
+            let num_known = if i == current { known.len() } else { 0 };
+            for _ in stack.len()+num_known..n {
+                stack.extend(pool.pop());
+            }
+            if i == current {
+                stack.extend(&known);
+            }
+            stack
+        }).collect();
+
+        State {
+            board: view.board,
+            positions: view.positions.clone(),
+            objectives,
+            rules: view.rules.clone(),
+            current,
+            pushed: view.pushed,
+            winner: None
+        }
+    }
+
+    fn targets(&self, player: usize) -> &[TileContent] {
+        self.rules.targets(&self.objectives[player])
+    }
+
+    fn actions(&self) -> Vec<Action> {
+        if !self.pushed {
+            self.board.distinct_legal_pushes(&self.rules).into_iter().map(|(e, o)| Action::Push(e, o)).collect()
+        } else {
+            let (row, col) = self.positions[self.current];
+            let mut cells: Vec<_> = self.board.reachable_from(row, col).into_iter().collect();
+            cells.sort();
+            let targets = self.targets(self.current);
+            cells.sort_by_key(|c| distance_to(&self.board, *c, targets));
+            let collecting = cells.iter().take_while(|c| distance_to(&self.board, **c, targets) == Some(0)).count();
+            cells.into_iter().take(if collecting > 0 { collecting } else { MAX_MOVES }).map(Action::Move).collect()
+        }
+    }
+
+    // Play an action, that must be legal.
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Push(entry, orientation) => {
+                let mut positions: Vec<_> = self.positions.iter_mut().collect();
+                self.board.push_tile(entry, orientation, &mut positions, &self.rules);
+                self.pushed = true;
+            },
+            Action::Move(pos) => {
+                self.positions[self.current] = pos;
+                self.pushed = false;
+
+                let content = self.board.content_at(pos.0, pos.1);
+                if self.targets(self.current).contains(&content) {
+                    let objectives = &mut self.objectives[self.current];
+                    let i = objectives.iter().rposition(|o| *o == content).unwrap();
+                    let done = objectives.remove(i);
+                    if objectives.is_empty() {
+                        self.winner = Some(self.current);
+                        return;
+                    }
+                    self.board.remove_content(done);
+                }
+                self.current = (self.current + 1) % self.positions.len();
+            }
+        }
+    }
+
+    // Finish the turn of the current player at random.
+    fn play_random_turn<R: Rng>(&mut self, rng: &mut R) {
+        if !self.pushed {
+            let (entry, orientation) = *self.board.distinct_legal_pushes(&self.rules).choose(rng).unwrap();
+            self.apply(Action::Push(entry, orientation));
+        }
+
+        let (row, col) = self.positions[self.current];
+        let mut cells: Vec<_> = self.board.reachable_from(row, col).into_iter().collect();
+        // Sort the cells, so that the choice only depends on the seed.
+        cells.sort();
+        let pos = *cells.choose(rng).unwrap();
+        self.apply(Action::Move(pos));
+    }
+
+    // The result of the game for each player: 1 for the winner, or, if the
+    // game is not over, up to 0.5 depending on the objectives collected
+    // since the start of the search.
+    fn rewards(&self, start: &[usize]) -> Vec<f64> {
+        match self.winner {
+            Some(w) => (0..start.len()).map(|i| if i == w { 1.0 } else { 0.0 }).collect(),
+            None => start.iter().zip(&self.objectives)
+                .map(|(n, o)| 0.5 * (n - o.len()) as f64 / *n as f64)
+                .collect()
+        }
+    }
+}
+
+struct Node {
+    // The action leading to this node, and the player who played it.
+    action: Option<Action>,
+    player: usize,
+    visits: u32,
+    value: f64,
+    children: Vec<usize>,
+    untried: Vec<Action>
+}
+
+impl Node {
+    fn new(action: Option<Action>, player: usize, untried: Vec<Action>) -> Node {
+        Node { action, player, visits: 0, value: 0.0, children: vec![], untried }
+    }
+}
+
+// A bot that searches the game tree with Monte Carlo Tree Search. The hidden
+// objectives of the opponents are sampled again at each iteration.
+pub struct MctsStrategy {
+    budget: Budget,
+    rng: StdRng,
+    // The move found together with the last push.
+    planned_move: Option<(usize, usize)>
+}
+
+impl MctsStrategy {
+    pub fn new(budget: Budget) -> MctsStrategy {
+        MctsStrategy { budget, rng: StdRng::from_rng(thread_rng()).unwrap(), planned_move: None }
+    }
+
+    pub fn with_seed(budget: Budget, seed: u64) -> MctsStrategy {
+        MctsStrategy { budget, rng: StdRng::seed_from_u64(seed), planned_move: None }
+    }
+
+    // Search from the current state of the game, returning the tree.
+    fn search(&mut self, view: &PlayerView) -> Vec<Node> {
+        let start = view.num_objectives.clone();
+        let root_state = State::sample(view, &mut self.rng);
+        let mut tree = vec![Node::new(None, root_state.current, root_state.actions())];
+
+        let started = Instant::now();
+        // At least one iteration is needed to have an action to choose.
+        let mut iterations = 0;
+        loop {
+            match self.budget {
+                Budget::Iterations(n) if iterations >= n.max(1) => break,
+                Budget::Time(t) if iterations > 0 && started.elapsed() >= t => break,
+                _ => iterations += 1
+            }
+
+            let mut state = State::sample(view, &mut self.rng);
+            let mut path = vec![0];
+            let mut node = 0;
+
+            // Selection: go down the tree while all the actions are expanded.
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() && state.winner.is_none() {
+                let parent_visits = f64::from(tree[node].visits);
+                node = *tree[node].children.iter().max_by(|&&a, &&b| {
+                    f64::total_cmp(&uct(&tree[a], parent_visits), &uct(&tree[b], parent_visits))
+                }).unwrap();
+                state.apply(tree[node].action.unwrap());
+                path.push(node);
+            }
+
+            // Expansion: add one of the actions that were never tried.
+            if state.winner.is_none() && !tree[node].untried.is_empty() {
+                let i = self.rng.gen_range(0, tree[node].untried.len());
+                let action = tree[node].untried.swap_remove(i);
+                let player = state.current;
+                state.apply(action);
+
+                let untried = if state.winner.is_none() { state.actions() } else { vec![] };
+                tree.push(Node::new(Some(action), player, untried));
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                path.push(child);
+            }
+
+            // Playout.
+            let mut turns = 0;
+            while state.winner.is_none() && turns < MAX_PLAYOUT_TURNS {
+                state.play_random_turn(&mut self.rng);
+                turns += 1;
+            }
+
+            // Backpropagation: each node is scored for the player who chose it.
+            let rewards = state.rewards(&start);
+            for n in path {
+                tree[n].visits += 1;
+                tree[n].value += rewards[tree[n].player];
+            }
+        }
+        tree
+    }
+}
+
+fn uct(node: &Node, parent_visits: f64) -> f64 {
+    let visits = f64::from(node.visits);
+    node.value / visits + EXPLORATION * (parent_visits.ln() / visits).sqrt()
+}
+
+// The most visited child of a node.
+fn best_child(tree: &[Node], node: usize) -> Option<usize> {
+    tree[node].children.iter().copied().max_by_key(|c| tree[*c].visits)
+}
+
+impl Strategy for MctsStrategy {
+    fn choose_push(&mut self, view: &PlayerView) -> (EntryPoint, Orientation) {
+        let tree = self.search(view);
+        let push = best_child(&tree, 0).unwrap();
+        self.planned_move = best_child(&tree, push).and_then(|m| match tree[m].action {
+            Some(Action::Move(pos)) => Some(pos),
+            _ => None
+        });
+
+        match tree[push].action {
+            Some(Action::Push(entry, orientation)) => (entry, orientation),
+            _ => unreachable!()
+        }
+    }
+
+    fn choose_move(&mut self, view: &PlayerView, reachable: &HashSet<(usize, usize)>) -> (usize, usize) {
+        match self.planned_move.take() {
+            Some(pos) if reachable.contains(&pos) => pos,
+            _ => {
+                let tree = self.search(view);
+                match best_child(&tree, 0).and_then(|m| tree[m].action) {
+                    Some(Action::Move(pos)) => pos,
+                    _ => unreachable!()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::objects_reachable_in_1_move_from;
+    use crate::game::{Game, MoveOutcome};
+    use crate::strategy::play_turn;
+
+    #[test]
+    fn zero_iterations() {
+        let mut game = Game::with_seed(&['r', 'b'], 1).unwrap();
+        let mut bot = MctsStrategy::with_seed(Budget::Iterations(0), 1);
+        for _ in 0..4 {
+            play_turn(&mut game, &mut bot).unwrap();
+        }
+    }
+
+    #[test]
+    fn collects_a_reachable_objective() {
+        // With a single objective and no going back home, collecting it
+        // wins the game.
+        let rules = RuleSet { objectives: vec![1], return_home: false, ..RuleSet::default() };
+        let mut found = 0;
+        for seed in 0..20 {
+            let mut game = Game::with_rules(&['r', 'b'], &rules, seed).unwrap();
+            let (row, col) = game.current_position();
+            let objective = game.current_objectives()[0];
+            if !objects_reachable_in_1_move_from(game.board(), game.rules(), row, col).contains_key(&objective) {
+                continue;
+            }
+            found += 1;
+
+            let mut bot = MctsStrategy::with_seed(Budget::Iterations(500), seed);
+            assert_eq!(play_turn(&mut game, &mut bot).unwrap(), MoveOutcome::Won, "seed {}", seed);
+        }
+        assert!(found > 0);
+    }
+}
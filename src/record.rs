@@ -0,0 +1,283 @@
+use crate::board::{Board, EntryPoint, Orientation, TileContent};
+use crate::game::{Action, Game, GameError};
+use crate::rules::RuleSet;
+use std::fmt;
+
+// A record is the transcript of a game: a few header tags, followed by the
+// numbered turns, each made of a push and a move, e.g.:
+//
+//   [Seed "1234"]
+//   [Players "r,b"]
+//   [Variant "Standard"]
+//   [Size "7"]
+//   [Objectives "5,3"]
+//   [WrapAround "no"]
+//
+//   1. 1e 35 2. 11n Sk 3. 4w 77
+//
+// The variant is "Standard" or "AnyOrder" (objectives collected in any
+// order). The other rules (see RuleSet) are only given when they are not
+// the default ones: the size of the board is 7 if it is not given.
+//
+// Pushes and moves use the same vocabulary as the terminal. Moves can be
+// given as coordinates ("35") or as the object on the target tile ("Sk"),
+// but they are always written as coordinates.
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    pub seed: u64,
+    pub players: Vec<char>,
+    pub rules: RuleSet,
+    pub actions: Vec<Action>
+}
+
+// The header tags of the rules, with the corresponding settings of RuleSet.
+// Several values are separated by commas.
+const RULE_TAGS: [(&str, &str); 7] = [
+    ("Variant", "variant"),
+    ("Size", "size"),
+    ("Objectives", "objectives"),
+    ("ReversePush", "reverse-push"),
+    ("WrapAround", "wrap-around"),
+    ("ReturnHome", "return-home"),
+    ("PlayerCount", "players")
+];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RecordError {
+    // The text does not follow the record format.
+    Syntax(String),
+    // The record contains an illegal action in the given turn.
+    Illegal(usize, GameError)
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Syntax(msg) => write!(f, "Invalid game record: {}", msg),
+            RecordError::Illegal(turn, e) => write!(f, "Illegal action in turn {}: {}", turn, e)
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+// Parse a push such as "1e", on a board of the given size.
+pub(crate) fn parse_push(s: &str, size: usize) -> Option<(EntryPoint, Orientation)> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (num, orientation) = s.split_at(split);
+
+    let mut chars = orientation.chars();
+    match (chars.next().and_then(Orientation::try_from_char), chars.next()) {
+        (Some(orientation), None) => Some((EntryPoint::try_from_num(num.parse().ok()?, size)?, orientation)),
+        _ => None
+    }
+}
+
+// Parse a position given as coordinates, such as "35".
+pub(crate) fn parse_position(s: &str) -> Option<(usize, usize)> {
+    let d: Vec<_> = s.chars().map(|c| c.to_digit(10)).collect::<Option<_>>()?;
+    match d.as_slice() {
+        [row, col] if *row > 0 && *col > 0 => Some(((row-1) as usize, (col-1) as usize)),
+        _ => None
+    }
+}
+
+// Parse a move, given either as coordinates or as an object on the board.
+fn parse_move(s: &str, board: &Board) -> Option<(usize, usize)> {
+    match TileContent::try_from_str(s) {
+        Some(obj) => board.content_position(obj),
+        None => parse_position(s)
+    }
+}
+
+impl Record {
+    pub fn from_game(game: &Game) -> Record {
+        Record {
+            seed: game.seed(),
+            players: game.players(),
+            rules: game.rules().clone(),
+            actions: game.history().to_vec()
+        }
+    }
+
+    // The game as it was before the first turn.
+    pub fn initial_game(&self) -> Result<Game, GameError> {
+        Game::with_rules(&self.players, &self.rules, self.seed)
+    }
+
+    // Play all the actions of the record, returning the final state.
+    pub fn replay(&self) -> Result<Game, RecordError> {
+        let mut game = self.initial_game().map_err(|e| RecordError::Illegal(0, e))?;
+
+        for (i, action) in self.actions.iter().enumerate() {
+            game.apply(*action).map_err(|e| RecordError::Illegal(i/2+1, e))?;
+        }
+        Ok(game)
+    }
+
+    // Read a record, checking that all the actions in it are legal.
+    pub fn parse(s: &str) -> Result<Record, RecordError> {
+        let syntax = |msg: String| RecordError::Syntax(msg);
+
+        let (mut seed, mut players, mut has_variant) = (None, None, false);
+        let mut rules = RuleSet::default();
+        let mut turns = vec![];
+
+        for line in s.lines() {
+            let line = line.trim();
+            if !line.starts_with('[') {
+                turns.extend(line.split_whitespace());
+                continue;
+            }
+
+            // Header tag: [Key "Value"]
+            let tag = line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+                .ok_or_else(|| syntax(format!("invalid tag {}", line)))?;
+            let (key, value) = tag.split_at(tag.find(' ').unwrap_or(tag.len()));
+            let value = value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                .ok_or_else(|| syntax(format!("invalid tag {}", line)))?;
+
+            match key {
+                "Seed" => seed = Some(value.parse().map_err(|_| syntax(format!("invalid seed {}", value)))?),
+                "Players" => players = Some(value.split(',').map(|p| {
+                    let mut chars = p.trim().chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Ok(c),
+                        _ => Err(syntax(format!("invalid player {}", p)))
+                    }
+                }).collect::<Result<Vec<_>, _>>()?),
+                // Other tags (event, date...) are allowed and ignored.
+                _ => if let Some((_, setting)) = RULE_TAGS.iter().find(|(tag, _)| *tag == key) {
+                    let values: Vec<_> = value.split(',').map(|v| v.trim()).collect();
+                    rules.set(setting, &values).unwrap().map_err(|msg| syntax(format!("{} in tag {}", msg, line)))?;
+                    has_variant |= key == "Variant";
+                }
+            }
+        }
+
+        let mut record = Record {
+            seed: seed.ok_or_else(|| syntax("missing seed".to_string()))?,
+            players: players.ok_or_else(|| syntax("missing players".to_string()))?,
+            rules,
+            actions: vec![]
+        };
+        if !has_variant {
+            return Err(syntax("missing variant".to_string()));
+        }
+
+        // Replay the game while reading, as moves can refer to objects
+        // on the board.
+        let mut game = record.initial_game().map_err(|e| RecordError::Illegal(0, e))?;
+        let mut turn = 0;
+
+        for token in turns {
+            if let Some(num) = token.strip_suffix('.') {
+                if game.has_pushed() || num.parse() != Ok(turn+1) {
+                    return Err(syntax(format!("unexpected turn number {}", token)));
+                }
+                turn += 1;
+                continue;
+            }
+
+            let action = if !game.has_pushed() {
+                if turn == record.actions.len()/2 {
+                    return Err(syntax(format!("missing turn number before {}", token)));
+                }
+                parse_push(token, record.rules.size).map(|(entry, orientation)| Action::Push(entry, orientation))
+            } else {
+                parse_move(token, game.board()).map(Action::Move)
+            };
+
+            let action = action.ok_or_else(|| syntax(format!("invalid action {} in turn {}", token, turn)))?;
+            game.apply(action).map_err(|e| RecordError::Illegal(turn, e))?;
+            record.actions.push(action);
+        }
+
+        Ok(record)
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let players: Vec<_> = self.players.iter().map(|p| p.to_string()).collect();
+
+        writeln!(f, "[Seed \"{}\"]", self.seed)?;
+        writeln!(f, "[Players \"{}\"]", players.join(","))?;
+
+        // The variant and the size are always given.
+        let settings = self.rules.settings();
+        let defaults = RuleSet::default().settings();
+        for (tag, setting) in RULE_TAGS.iter() {
+            if let Some(rule) = settings.iter().find(|(s, _)| s == setting) {
+                if ["variant", "size"].contains(setting) || !defaults.contains(rule) {
+                    writeln!(f, "[{} \"{}\"]", tag, rule.1.join(","))?;
+                }
+            }
+        }
+        writeln!(f)?;
+
+        // A few turns per line.
+        for (i, turn) in self.actions.chunks(2).enumerate() {
+            if i > 0 {
+                write!(f, "{}", if i % 6 == 0 { "\n" } else { " " })?;
+            }
+            write!(f, "{}.", i+1)?;
+            for action in turn {
+                write!(f, " {}", action.notation(self.rules.size))?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::{play_turn, RandomStrategy};
+
+    fn header(seed: u64) -> String {
+        format!("[Seed \"{}\"]\n[Players \"r,b\"]\n[Variant \"Standard\"]\n\n", seed)
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::with_seed(&['r', 'b'], 7).unwrap();
+        let mut bot = RandomStrategy::with_seed(7);
+        for _ in 0..20 {
+            play_turn(&mut game, &mut bot).unwrap();
+        }
+
+        let record = Record::from_game(&game);
+        let parsed = Record::parse(&record.to_string()).unwrap();
+        assert_eq!(parsed, record);
+        assert_eq!(parsed.replay().unwrap().save_to_string(), game.save_to_string());
+    }
+
+    #[test]
+    fn reverse_push_is_illegal() {
+        let mut game = Game::with_seed(&['r', 'b'], 7).unwrap();
+        let entry = EntryPoint::North(1);
+        game.apply_push(entry, Orientation::North).unwrap();
+        let pos = game.current_position();
+
+        let push = Action::Push(entry, Orientation::North).notation(7);
+        let reverse = Action::Push(entry.opposite(), Orientation::North).notation(7);
+        let text = format!("{}1. {} {} 2. {} 11", header(7), push, Action::Move(pos).notation(7), reverse);
+        assert_eq!(Record::parse(&text), Err(RecordError::Illegal(2, GameError::ReversePush(entry.opposite()))));
+    }
+
+    #[test]
+    fn unreachable_move_is_illegal() {
+        let mut game = Game::with_seed(&['r', 'b'], 7).unwrap();
+        let push = Action::Push(EntryPoint::North(1), Orientation::North);
+        game.apply(push).unwrap();
+        let (row, col) = game.current_position();
+        let reachable = game.board().reachable_from(row, col);
+        let cell = (0..7).flat_map(|r| (0..7).map(move |c| (r, c))).find(|c| !reachable.contains(c)).unwrap();
+
+        let text = format!("{}1. {} {}", header(7), push.notation(7), Action::Move(cell).notation(7));
+        assert_eq!(Record::parse(&text), Err(RecordError::Illegal(1, GameError::Unreachable(cell))));
+    }
+}
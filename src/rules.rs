@@ -0,0 +1,285 @@
+use crate::board::{Board, TileContent};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// The rules of a game. The default ones are those of the original game, and
+// house rules can be given in a configuration file, one rule per line:
+//
+//   # Short games on a small board.
+//   size 5
+//   variant AnyOrder
+//   objectives 3
+//   reverse-push yes
+//   wrap-around no
+//   return-home no
+//   players 1 4
+//
+// The rules that are not given keep their default value. The number of
+// objectives is either the same for all the players, or given for each of
+// them (in the order in which they play) for handicaps.
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RuleSet {
+    // The size of the board (5, 7 or 9).
+    pub size: usize,
+    pub variant: Variant,
+    // The number of objectives dealt to the players, or nothing to split
+    // all the objects evenly.
+    pub objectives: Vec<usize>,
+    // Whether a push can revert the previous one.
+    pub reverse_push: bool,
+    // Whether the players pushed out of the board come back on the other
+    // side. Otherwise, they stay at the edge.
+    pub wrap_around: bool,
+    // Whether the players have to go back to their start tile to win, once
+    // their objectives are collected.
+    pub return_home: bool,
+    pub min_players: usize,
+    pub max_players: usize
+}
+
+// The rules about the order in which the objectives are collected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    // The objectives are a face-down stack, collected from the top.
+    #[default]
+    Standard,
+    // The objectives are a hand, collected in any order before going home.
+    AnyOrder
+}
+
+impl Variant {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::AnyOrder => "AnyOrder"
+        }
+    }
+
+    pub fn try_from_str(s: &str) -> Option<Variant> {
+        match s {
+            "Standard" => Some(Variant::Standard),
+            "AnyOrder" => Some(Variant::AnyOrder),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RulesError {
+    Io(io::Error),
+    // A line that could not be understood.
+    Parse(usize, String)
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesError::Io(e) => write!(f, "Cannot read the rules: {}", e),
+            RulesError::Parse(line, msg) => write!(f, "Invalid rules, line {}: {}", line, msg)
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl From<io::Error> for RulesError {
+    fn from(e: io::Error) -> Self {
+        RulesError::Io(e)
+    }
+}
+
+pub(crate) fn bool_from_str(s: &str) -> Option<bool> {
+    match s {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None
+    }
+}
+
+pub(crate) fn yes_no(b: bool) -> &'static str {
+    if b { "yes" } else { "no" }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            size: 7,
+            variant: Variant::Standard,
+            objectives: vec![],
+            reverse_push: false,
+            wrap_around: true,
+            return_home: true,
+            min_players: 2,
+            max_players: 4
+        }
+    }
+}
+
+impl RuleSet {
+    // The objectives that can be collected now, out of a stack whose top is
+    // last: the top one, or any of them in the any-order variant. Going back
+    // home, at the bottom, is always last.
+    pub(crate) fn targets<'a>(&self, objectives: &'a [TileContent]) -> &'a [TileContent] {
+        let n = objectives.len();
+        let home = self.return_home as usize;
+        match self.variant {
+            Variant::AnyOrder if n > home => &objectives[home..],
+            _ => &objectives[n.saturating_sub(1)..]
+        }
+    }
+
+    // The rules as settings of the configuration file, in order.
+    pub(crate) fn settings(&self) -> Vec<(&'static str, Vec<String>)> {
+        let mut res = vec![
+            ("size", vec![self.size.to_string()]),
+            ("variant", vec![self.variant.to_str().to_string()])
+        ];
+        if !self.objectives.is_empty() {
+            res.push(("objectives", self.objectives.iter().map(|n| n.to_string()).collect()));
+        }
+        res.push(("reverse-push", vec![yes_no(self.reverse_push).to_string()]));
+        res.push(("wrap-around", vec![yes_no(self.wrap_around).to_string()]));
+        res.push(("return-home", vec![yes_no(self.return_home).to_string()]));
+        res.push(("players", vec![self.min_players.to_string(), self.max_players.to_string()]));
+        res
+    }
+
+    // Change one of the rules, given as a setting of the configuration
+    // file. Nothing is returned if the setting is not a rule.
+    pub(crate) fn set(&mut self, key: &str, values: &[&str]) -> Option<Result<(), String>> {
+        let yes_no = |values: &[&str]| match values {
+            [x] => bool_from_str(x).ok_or_else(|| "expected \"yes\" or \"no\"".to_string()),
+            _ => Err("expected \"yes\" or \"no\"".to_string())
+        };
+
+        let res = match (key, values) {
+            ("size", [x]) => x.parse().ok().filter(|n| Board::is_valid_size(*n))
+                .map(|n| self.size = n).ok_or_else(|| "invalid size".to_string()),
+            ("variant", [x]) => Variant::try_from_str(x)
+                .map(|v| self.variant = v).ok_or_else(|| "unknown variant".to_string()),
+            ("objectives", counts) => counts.iter().map(|x| x.parse().ok().filter(|n| *n > 0)).collect::<Option<_>>()
+                .map(|counts| self.objectives = counts).ok_or_else(|| "invalid number of objectives".to_string()),
+            ("reverse-push", values) => yes_no(values).map(|b| self.reverse_push = b),
+            ("wrap-around", values) => yes_no(values).map(|b| self.wrap_around = b),
+            ("return-home", values) => yes_no(values).map(|b| self.return_home = b),
+            ("players", [min, max]) => match (min.parse(), max.parse()) {
+                (Ok(min), Ok(max)) if 1 <= min && min <= max && max <= 4 => {
+                    self.min_players = min;
+                    self.max_players = max;
+                    Ok(())
+                },
+                _ => Err("the number of players should be between 1 and 4".to_string())
+            },
+            ("size", _) | ("variant", _) | ("players", _) => Err(format!("invalid {}", key)),
+            _ => return None
+        };
+        Some(res)
+    }
+
+    pub fn from_config_str(s: &str) -> Result<RuleSet, RulesError> {
+        let mut rules = RuleSet::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let words: Vec<_> = line.split_whitespace().collect();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+
+            match rules.set(words[0], &words[1..]) {
+                Some(Ok(())) => {},
+                Some(Err(msg)) => return Err(RulesError::Parse(i+1, msg)),
+                None => return Err(RulesError::Parse(i+1, format!("unknown rule \"{}\"", words[0])))
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RuleSet, RulesError> {
+        RuleSet::from_config_str(&fs::read_to_string(path)?)
+    }
+}
+
+// The rules are written in the format of the configuration file.
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, values) in self.settings() {
+            writeln!(f, "{} {}", key, values.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let rules = [
+            RuleSet::default(),
+            RuleSet {
+                size: 5,
+                variant: Variant::AnyOrder,
+                objectives: vec![3, 5],
+                reverse_push: true,
+                wrap_around: false,
+                return_home: false,
+                min_players: 1,
+                max_players: 3
+            },
+            RuleSet { size: 9, objectives: vec![8], ..RuleSet::default() }
+        ];
+        for r in &rules {
+            assert_eq!(&RuleSet::from_config_str(&r.to_string()).unwrap(), r);
+        }
+    }
+
+    #[test]
+    fn config_file() {
+        let rules = RuleSet::from_config_str("# Short games.\n\nsize 5\n  objectives 2 3\nreturn-home no\n").unwrap();
+        assert_eq!(rules, RuleSet { size: 5, objectives: vec![2, 3], return_home: false, ..RuleSet::default() });
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let error_line = |s: &str| match RuleSet::from_config_str(s) {
+            Err(RulesError::Parse(line, _)) => line,
+            res => panic!("{:?} gives {:?}", s, res)
+        };
+
+        assert_eq!(error_line("size 6"), 1);
+        assert_eq!(error_line("size 7 9"), 1);
+        assert_eq!(error_line("variant Random"), 1);
+        assert_eq!(error_line("objectives 0"), 1);
+        assert_eq!(error_line("objectives 3 x"), 1);
+        assert_eq!(error_line("reverse-push maybe"), 1);
+        assert_eq!(error_line("wrap-around"), 1);
+        assert_eq!(error_line("return-home yes no"), 1);
+        assert_eq!(error_line("players 0 4"), 1);
+        assert_eq!(error_line("players 3 2"), 1);
+        assert_eq!(error_line("players 2 5"), 1);
+        assert_eq!(error_line("players 2"), 1);
+        assert_eq!(error_line("size 5\n# A comment.\nspeed 2"), 3);
+
+        match RuleSet::from_config_str("size 5\nhouse-rule yes") {
+            Err(RulesError::Parse(2, msg)) => assert_eq!(msg, "unknown rule \"house-rule\""),
+            res => panic!("unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn set_only_knows_rules() {
+        let mut rules = RuleSet::default();
+        assert_eq!(rules.set("seed", &["1"]), None);
+        assert_eq!(rules.set("players", &["1", "1"]), Some(Ok(())));
+        assert_eq!((rules.min_players, rules.max_players), (1, 1));
+        assert!(matches!(rules.set("players", &["2", "1"]), Some(Err(_))));
+        assert_eq!((rules.min_players, rules.max_players), (1, 1));
+    }
+}
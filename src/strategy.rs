@@ -0,0 +1,247 @@
+use crate::analyzer::objects_reachable_in_1_move_from;
+use crate::board::{Board, EntryPoint, Orientation, TileContent};
+use crate::game::{Game, GameError, MoveOutcome, PlayerView};
+use rand::{thread_rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+
+// A way of playing the turns of a player, such as a bot. A turn is made of
+// a push, chosen first, and then of a move to one of the reachable cells.
+// Strategies only see the game from the point of view of their player.
+pub trait Strategy {
+    fn choose_push(&mut self, view: &PlayerView) -> (EntryPoint, Orientation);
+
+    fn choose_move(&mut self, view: &PlayerView, reachable: &HashSet<(usize, usize)>) -> (usize, usize);
+}
+
+// Play a full turn of the current player with the given strategy.
+pub fn play_turn(game: &mut Game, strategy: &mut dyn Strategy) -> Result<MoveOutcome, GameError> {
+    let (entry, orientation) = strategy.choose_push(&game.current_view());
+    game.apply_push(entry, orientation)?;
+
+    let (row, col) = game.current_position();
+    let reachable = game.board().reachable_from(row, col);
+    let next_pos = strategy.choose_move(&game.current_view(), &reachable);
+    game.apply_move(next_pos)
+}
+
+// A bot that plays at random.
+pub struct RandomStrategy {
+    rng: StdRng
+}
+
+impl RandomStrategy {
+    pub fn new() -> RandomStrategy {
+        RandomStrategy { rng: StdRng::from_rng(thread_rng()).unwrap() }
+    }
+
+    pub fn with_seed(seed: u64) -> RandomStrategy {
+        RandomStrategy { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Default for RandomStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn choose_push(&mut self, view: &PlayerView) -> (EntryPoint, Orientation) {
+        *view.board.legal_pushes(&view.rules).choose(&mut self.rng).unwrap()
+    }
+
+    fn choose_move(&mut self, _view: &PlayerView, reachable: &HashSet<(usize, usize)>) -> (usize, usize) {
+        let mut reachable: Vec<_> = reachable.iter().copied().collect();
+        // Sort the cells, so that the choice only depends on the seed.
+        reachable.sort();
+        *reachable.choose(&mut self.rng).unwrap()
+    }
+}
+
+// A bot that goes to an objective whenever it can reach one in one turn,
+// and otherwise gets as close to one as it can. Ties are broken at random,
+// so that two greedy bots do not repeat the same positions forever.
+pub struct GreedyStrategy {
+    rng: StdRng
+}
+
+impl GreedyStrategy {
+    pub fn new() -> GreedyStrategy {
+        GreedyStrategy { rng: StdRng::from_rng(thread_rng()).unwrap() }
+    }
+
+    pub fn with_seed(seed: u64) -> GreedyStrategy {
+        GreedyStrategy { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Default for GreedyStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The distance between two cells, as if there were no walls.
+pub(crate) fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+// The distance from a cell to the closest of the objects on the board, if
+// any of them is (the others are on the extra tile).
+pub(crate) fn distance_to(board: &Board, cell: (usize, usize), objs: &[TileContent]) -> Option<usize> {
+    objs.iter().filter_map(|o| board.content_position(*o)).map(|target| distance(cell, target)).min()
+}
+
+// The reachable cell closest to one of the objects, with its distance. If no
+// object is on the board, all the cells are as good.
+pub(crate) fn closest_cell(board: &Board, reachable: &HashSet<(usize, usize)>, objs: &[TileContent]) -> ((usize, usize), usize) {
+    let mut cells: Vec<_> = reachable.iter().copied().collect();
+    cells.sort();
+    cells.into_iter().map(|c| (c, distance_to(board, c, objs).unwrap_or(0))).min_by_key(|x| x.1).unwrap()
+}
+
+impl Strategy for GreedyStrategy {
+    fn choose_push(&mut self, view: &PlayerView) -> (EntryPoint, Orientation) {
+        let board = &view.board;
+        let (row, col) = view.position();
+        let objs = &view.objectives;
+
+        // If an objective can be reached, only the pushes that allow it
+        // are considered, and one of them will leave it at distance 0.
+        let reach = objects_reachable_in_1_move_from(board, &view.rules, row, col);
+        let entries = objs.iter().find_map(|o| reach.get(o));
+
+        let mut best = vec![];
+        let mut best_d = usize::MAX;
+        for (entry, orientation) in board.distinct_legal_pushes(&view.rules) {
+            if entries.is_some_and(|e| !e.contains(&entry)) {
+                continue;
+            }
+
+            let mut new_board = *board;
+            let mut pos = (row, col);
+            new_board.push_tile(entry, orientation, &mut [&mut pos], &view.rules);
+            // Pushing all the objectives off the board is the worst choice.
+            let d = new_board.reachable_from(pos.0, pos.1).into_iter()
+                .filter_map(|c| distance_to(&new_board, c, objs))
+                .min().unwrap_or(usize::MAX);
+            if d < best_d {
+                best.clear();
+                best_d = d;
+            }
+            if d == best_d {
+                best.push((entry, orientation));
+            }
+        }
+        *best.choose(&mut self.rng).unwrap()
+    }
+
+    fn choose_move(&mut self, view: &PlayerView, reachable: &HashSet<(usize, usize)>) -> (usize, usize) {
+        let board = &view.board;
+        let objs = &view.objectives;
+        let (_, d) = closest_cell(board, reachable, objs);
+
+        // Among the cells that are as close to an objective, take any of the
+        // ones with the shortest path (sorted, so that the choice only
+        // depends on the seed).
+        let from = view.position();
+        let path_len = |c: (usize, usize)| board.shortest_path(from, c).map_or(usize::MAX, |p| p.len());
+        let mut cells: Vec<_> = reachable.iter().copied()
+            .filter(|c| distance_to(board, *c, objs).is_none_or(|x| x == d))
+            .map(|c| (c, path_len(c)))
+            .collect();
+        let shortest = cells.iter().map(|x| x.1).min().unwrap();
+        cells.retain(|x| x.1 == shortest);
+        cells.sort();
+        cells.choose(&mut self.rng).unwrap().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    // Play a game between two greedy bots, returning the number of turns.
+    fn greedy_game(size: usize, seed: u64) -> Option<usize> {
+        let mut game = Game::with_rules(&['r', 'b'], &RuleSet { size, ..RuleSet::default() }, seed).unwrap();
+        let mut bots = [GreedyStrategy::with_seed(seed), GreedyStrategy::with_seed(seed + 1)];
+        for turn in 0..1500 {
+            if play_turn(&mut game, &mut bots[turn % 2]).unwrap() == MoveOutcome::Won {
+                return Some(turn + 1);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn greedy_bot_keeps_its_objective_on_the_board() {
+        // Whether a push takes all the objectives off the board.
+        let ejects = |view: &PlayerView, (entry, orientation): (EntryPoint, Orientation)| {
+            let mut board = view.board;
+            board.push_tile(entry, orientation, &mut [], &view.rules);
+            view.objectives.iter().all(|o| board.content_position(*o).is_none())
+        };
+
+        for seed in 0..30 {
+            let mut game = Game::with_seed(&['r', 'b'], seed).unwrap();
+            let mut bot = GreedyStrategy::with_seed(seed);
+            for _ in 0..10 {
+                let view = game.current_view();
+                let push = bot.choose_push(&view);
+                if view.board.distinct_legal_pushes(&view.rules).into_iter().any(|m| !ejects(&view, m)) {
+                    assert!(!ejects(&view, push), "seed {}", seed);
+                }
+                game.apply_push(push.0, push.1).unwrap();
+
+                let (row, col) = game.current_position();
+                let reachable = game.board().reachable_from(row, col);
+                let pos = bot.choose_move(&game.current_view(), &reachable);
+                if game.apply_move(pos).unwrap() == MoveOutcome::Won {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_bot_takes_the_shortest_path() {
+        let mut found = 0;
+        for seed in 0..50 {
+            let game = Game::with_seed(&['r', 'b'], seed).unwrap();
+            let view = game.current_view();
+            let (row, col) = view.position();
+            let reachable = view.board.reachable_from(row, col);
+            let path_len = |c| view.board.shortest_path((row, col), c).unwrap().len();
+
+            // Only the positions where equally close cells have paths of
+            // different lengths are interesting.
+            let (_, d) = closest_cell(&view.board, &reachable, &view.objectives);
+            let lengths: HashSet<_> = reachable.iter().copied()
+                .filter(|c| distance_to(&view.board, *c, &view.objectives).is_none_or(|x| x == d))
+                .map(path_len)
+                .collect();
+            if lengths.len() < 2 {
+                continue;
+            }
+            found += 1;
+
+            let shortest = *lengths.iter().min().unwrap();
+            for bot_seed in 0..5 {
+                let cell = GreedyStrategy::with_seed(bot_seed).choose_move(&view, &reachable);
+                assert_eq!(path_len(cell), shortest, "seed {}", seed);
+            }
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn greedy_bots_finish() {
+        // Seed 2 used to loop forever on the standard board.
+        for &(size, seed) in &[(7, 2), (7, 5), (9, 0), (9, 1)] {
+            assert!(greedy_game(size, seed).is_some(), "size {}, seed {}", size, seed);
+        }
+    }
+}